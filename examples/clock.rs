@@ -0,0 +1,26 @@
+use std::f64::consts::PI;
+
+use ray_tracer_challenge::features::canvas::Canvas;
+use ray_tracer_challenge::features::color::Color;
+use ray_tracer_challenge::features::error::Result;
+use ray_tracer_challenge::features::matrix::Matrix;
+use ray_tracer_challenge::features::tuple::Tuple;
+
+fn main() -> Result<()> {
+    let mut canvas = Canvas::new(600, 600);
+    let white_color = Color::new(1.0, 1.0, 1.0);
+    let magenta_color = Color::new(1.0, 0.0, 1.0);
+
+    let center = Tuple::point(canvas.width as f64 / 2.0, canvas.height as f64 / 2.0, 0.0);
+    let translation = Matrix::translation(center.x, center.y, center.z);
+    let rotation = Matrix::rotation_z(PI / 30.0);
+    let mut transform = translation.clone() * rotation.clone();
+    for index in 0..60u32 {
+        let point = transform.clone() * Tuple::point(0.0, -(canvas.height as f64) / 3.0, 0.0);
+        let color = if index.is_multiple_of(5) { white_color } else { magenta_color };
+        canvas.draw_point(point.x.trunc() + 1.0, point.y.trunc() + 1.0, 2.0, color, 1.0);
+
+        transform = transform.clone() * rotation.clone();
+    }
+    canvas.canvas_to_file("clock.ppm")
+}
@@ -0,0 +1,181 @@
+use super::{shape::Shape, texture::ImageTexture, tuple::Tuple};
+
+// The step, in world units, for the finite differences taken across the surface.
+const STEP: f64 = 1e-4;
+
+// Surface detail that tilts the shading normal without changing the geometry.
+// Heights come from a noise function or from the brightness of an image, and
+// `strength` scales how steep they look. A normal map stores tangent-space normals
+// directly, red along the texture's u axis, green along v and blue out of the
+// surface, each mapped from 0..1 to -1..1.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Bump {
+    Noise { scale: f64, strength: f64 },
+    HeightMap { texture: ImageTexture, strength: f64 },
+    NormalMap { texture: ImageTexture },
+}
+
+impl Bump {
+    // `normal` is the unperturbed outward world space normal at `point` on `object`.
+    pub fn perturb(&self, object: &Shape, point: Tuple, normal: Tuple) -> Tuple {
+        let (tangent, bitangent) = normal.tangent_frame();
+        match self {
+            Bump::Noise { scale, strength } => {
+                let height = |p: Tuple| noise(object.inverse() * p * *scale);
+                tilt(normal, tangent, bitangent, point, *strength, height)
+            }
+            Bump::HeightMap { texture, strength } => {
+                let height = |p: Tuple| {
+                    let color = texture.color_at(object.inverse() * p);
+                    (color.red + color.green + color.blue) / 3.0
+                };
+                tilt(normal, tangent, bitangent, point, *strength, height)
+            }
+            Bump::NormalMap { texture } => {
+                let uv = |p: Tuple| texture.uv_at(object.inverse() * p);
+                let Some((u_axis, v_axis)) = uv_axes(normal, tangent, bitangent, point, uv) else { return normal };
+                let color = texture.color_at(object.inverse() * point);
+                let (x, y, z) = (color.red * 2.0 - 1.0, color.green * 2.0 - 1.0, color.blue * 2.0 - 1.0);
+                (u_axis * x + v_axis * y + normal * z).normalize()
+            }
+        }
+    }
+}
+
+// Leans the normal away from the direction the height field rises in.
+fn tilt<H: Fn(Tuple) -> f64>(normal: Tuple, tangent: Tuple, bitangent: Tuple, point: Tuple, strength: f64, height: H) -> Tuple {
+    let slope = |axis: Tuple| (height(point + axis * STEP) - height(point - axis * STEP)) / (2.0 * STEP);
+    (normal - (tangent * slope(tangent) + bitangent * slope(bitangent)) * strength).normalize()
+}
+
+// The surface directions along which u and v increase, found by inverting how uv
+// changes along the tangent frame. Differences are wrapped into -0.5..0.5 so a
+// mapping's seam does not look like a steep slope. None where the mapping is
+// degenerate, such as at a sphere's poles.
+fn uv_axes<M: Fn(Tuple) -> (f64, f64)>(normal: Tuple, tangent: Tuple, bitangent: Tuple, point: Tuple, uv: M) -> Option<(Tuple, Tuple)> {
+    let wrap = |difference: f64| (difference + 0.5).rem_euclid(1.0) - 0.5;
+    let derivative = |axis: Tuple| {
+        let ((u0, v0), (u1, v1)) = (uv(point - axis * STEP), uv(point + axis * STEP));
+        (wrap(u1 - u0) / (2.0 * STEP), wrap(v1 - v0) / (2.0 * STEP))
+    };
+    let ((du_dt, dv_dt), (du_db, dv_db)) = (derivative(tangent), derivative(bitangent));
+    let determinant = du_dt * dv_db - du_db * dv_dt;
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+    let u_axis = ((tangent * dv_db - bitangent * dv_dt) * determinant.signum()).normalize();
+    let v_axis = normal.cross(&u_axis) * determinant.signum();
+    Some((u_axis, v_axis))
+}
+
+// Perlin's improved gradient noise, roughly in -1..1 and zero at every integer
+// lattice point. Gradients come from hashing the lattice coordinates rather than a
+// permutation table, so there is nothing to build or share between threads.
+pub fn noise(point: Tuple) -> f64 {
+    let (x0, y0, z0) = (point.x.floor(), point.y.floor(), point.z.floor());
+    let (fx, fy, fz) = (point.x - x0, point.y - y0, point.z - z0);
+    let fade = |t: f64| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let lerp = |t: f64, a: f64, b: f64| a + t * (b - a);
+    let corner = |dx: f64, dy: f64, dz: f64| gradient(x0 + dx, y0 + dy, z0 + dz, fx - dx, fy - dy, fz - dz);
+    let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+    lerp(
+        w,
+        lerp(v, lerp(u, corner(0.0, 0.0, 0.0), corner(1.0, 0.0, 0.0)), lerp(u, corner(0.0, 1.0, 0.0), corner(1.0, 1.0, 0.0))),
+        lerp(v, lerp(u, corner(0.0, 0.0, 1.0), corner(1.0, 0.0, 1.0)), lerp(u, corner(0.0, 1.0, 1.0), corner(1.0, 1.0, 1.0))),
+    )
+}
+
+// The dot product of the offset (x, y, z) with one of the twelve edge directions
+// of a cube, picked by hashing the lattice point.
+fn gradient(ix: f64, iy: f64, iz: f64, x: f64, y: f64, z: f64) -> f64 {
+    let mut hash = (ix as i64 as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (iy as i64 as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ (iz as i64 as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+    hash ^= hash >> 29;
+    hash = hash.wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    hash ^= hash >> 32;
+    match hash % 12 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x + z,
+        5 => -x + z,
+        6 => x - z,
+        7 => -x - z,
+        8 => y + z,
+        9 => -y + z,
+        10 => y - z,
+        _ => -y - z,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::features::{
+        canvas::Canvas,
+        color::Color,
+        shape::ShapeKind,
+        texture::{TextureFilter, UvMapping},
+    };
+
+    #[test]
+    fn noise_is_smooth_and_vanishes_on_the_lattice() {
+        assert_eq!(noise(Tuple::point(3.0, -2.0, 7.0)), 0.0);
+        let mut range: (f64, f64) = (0.0, 0.0);
+        for i in 0..1000 {
+            let point = Tuple::point(i as f64 * 0.137, i as f64 * 0.071, i as f64 * 0.029);
+            let value = noise(point);
+            range = (range.0.min(value), range.1.max(value));
+            assert!((noise(point + Tuple::vector(1e-6, 0.0, 0.0)) - value).abs() < 1e-4);
+        }
+        assert!(range.0 < -0.3 && range.1 > 0.3 && range.0 >= -1.5 && range.1 <= 1.5);
+    }
+
+    #[test]
+    fn noise_tilts_the_normal_but_keeps_it_facing_out() {
+        let plane = Shape::new(ShapeKind::Plane);
+        let bump = Bump::Noise { scale: 4.0, strength: 0.2 };
+        let up = Tuple::vector(0.0, 1.0, 0.0);
+        let perturbed = bump.perturb(&plane, Tuple::point(0.3, 0.0, 0.7), up);
+        assert!((perturbed.magnitude() - 1.0).abs() < 1e-9);
+        assert!(perturbed != up && perturbed.dot(&up) > 0.9);
+        assert_eq!(Bump::Noise { scale: 4.0, strength: 0.0 }.perturb(&plane, Tuple::point(0.3, 0.0, 0.7), up), up);
+    }
+
+    #[test]
+    fn normals_lean_away_from_rising_heights() {
+        // Brightness, and so height, increases with u, which runs along x.
+        let mut ramp = Canvas::new(8, 1);
+        for x in 0..8 {
+            ramp.write_pixel(x, 0, Color::new(1.0, 1.0, 1.0) * (x as f64 / 7.0));
+        }
+        let texture = ImageTexture::new(Arc::new(ramp), UvMapping::Planar, TextureFilter::Bilinear);
+        let bump = Bump::HeightMap { texture, strength: 0.1 };
+        let normal = bump.perturb(&Shape::new(ShapeKind::Plane), Tuple::point(0.5, 0.0, 0.5), Tuple::vector(0.0, 1.0, 0.0));
+        assert!(normal.x < -0.05 && normal.z.abs() < 1e-6);
+    }
+
+    #[test]
+    fn normal_maps_follow_the_texture_axes() {
+        // Every texel leans towards +u.
+        let image = Arc::new(map_of(Color::new(0.8, 0.5, 0.9)));
+        let bump = Bump::NormalMap { texture: ImageTexture::new(image.clone(), UvMapping::Planar, TextureFilter::Nearest) };
+        let normal = bump.perturb(&Shape::new(ShapeKind::Plane), Tuple::point(0.25, 0.0, 0.25), Tuple::vector(0.0, 1.0, 0.0));
+        assert_eq!(normal, Tuple::vector(0.6, 0.8, 0.0));
+
+        // At the front of a sphere, right on the mapping's seam, u runs along +x.
+        let bump = Bump::NormalMap { texture: ImageTexture::new(image, UvMapping::Spherical, TextureFilter::Nearest) };
+        let normal = bump.perturb(&Shape::new(ShapeKind::Sphere), Tuple::point(0.0, 0.0, -1.0), Tuple::vector(0.0, 0.0, -1.0));
+        assert!((normal.x - 0.6).abs() < 1e-3 && (normal.z + 0.8).abs() < 1e-3, "{:?}", normal);
+    }
+
+    fn map_of(color: Color) -> Canvas {
+        let mut map = Canvas::new(1, 1);
+        map.write_pixel(0, 0, color);
+        map
+    }
+}
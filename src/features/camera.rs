@@ -0,0 +1,159 @@
+use super::{
+    canvas::Canvas,
+    color::Color,
+    error::Result,
+    matrix::Matrix,
+    path::Integrator,
+    random::Rng,
+    ray::Ray,
+    render::Renderer,
+    sampling::Sampler,
+    stats::RayKind,
+    tuple::Tuple,
+    world::World,
+};
+
+#[derive(Debug, Clone)]
+pub struct Camera {
+    pub width: usize,
+    pub height: usize,
+    pub field_of_view: f64,
+    transform: Matrix,
+    inverse: Matrix,
+    half_width: f64,
+    half_height: f64,
+    pixel_size: f64,
+}
+
+impl Camera {
+    pub fn new(width: usize, height: usize, field_of_view: f64) -> Self {
+        let half_view = (field_of_view / 2.0).tan();
+        let aspect = width as f64 / height as f64;
+        let (half_width, half_height) = if aspect >= 1.0 { (half_view, half_view / aspect) } else { (half_view * aspect, half_view) };
+        Self {
+            width,
+            height,
+            field_of_view,
+            transform: Matrix::identity(4),
+            inverse: Matrix::identity(4),
+            half_width,
+            half_height,
+            pixel_size: half_width * 2.0 / width as f64,
+        }
+    }
+
+    pub fn transform(&self) -> &Matrix {
+        &self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: Matrix) -> Result<()> {
+        self.inverse = transform.inverse()?;
+        self.transform = transform;
+        Ok(())
+    }
+
+    pub fn pixel_size(&self) -> f64 {
+        self.pixel_size
+    }
+
+    // The ray through canvas position (x, y); pixel (i, j) is centred on (i + 0.5, j + 0.5).
+    pub fn ray_for_pixel(&self, x: f64, y: f64) -> Ray {
+        let world_x = self.half_width - x * self.pixel_size;
+        let world_y = self.half_height - y * self.pixel_size;
+        let pixel = &self.inverse * Tuple::point(world_x, world_y, -1.0);
+        let origin = &self.inverse * Tuple::point(0.0, 0.0, 0.0);
+        Ray::new(origin, (pixel - origin).normalize())
+    }
+
+    pub fn render(&self, world: &World, renderer: &Renderer, sampler: &Sampler, max_depth: usize) -> Canvas {
+        self.render_with(world, renderer, sampler, Integrator::Whitted, max_depth)
+    }
+
+    // `max_depth` bounds the recursion for the Whitted tracer and the number of
+    // bounces for the path tracer. Path tracing seeds each sample from its canvas
+    // position, so it renders the same on any number of threads.
+    pub fn render_with(&self, world: &World, renderer: &Renderer, sampler: &Sampler, integrator: Integrator, max_depth: usize) -> Canvas {
+        renderer.render_sampled(self.width, self.height, sampler, self.shader(world, integrator, max_depth))
+    }
+
+    // The colour seen through canvas position (x, y). Hand it to
+    // Renderer::render_sampled_with_progress to watch or cancel a render.
+    pub fn shader<'a>(&'a self, world: &'a World, integrator: Integrator, max_depth: usize) -> impl Fn(f64, f64) -> Color + Sync + 'a {
+        move |x, y| {
+            world.counters.count_ray(RayKind::Camera);
+            let ray = self.ray_for_pixel(x, y);
+            match integrator {
+                Integrator::Whitted => world.color_at(&ray, max_depth),
+                Integrator::PathTracing => world.trace_path(&ray, max_depth, &mut Rng::new(x.to_bits() ^ y.to_bits().rotate_left(32))),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        f64::consts::PI,
+        sync::atomic::{AtomicBool, Ordering},
+    };
+
+    use super::*;
+    use crate::features::{render::Progress, util::almost_equal, world::tests::default_world};
+
+    #[test]
+    fn pixel_size_for_horizontal_and_vertical_canvases() {
+        assert!(almost_equal(Camera::new(200, 125, PI / 2.0).pixel_size(), 0.01));
+        assert!(almost_equal(Camera::new(125, 200, PI / 2.0).pixel_size(), 0.01));
+    }
+
+    #[test]
+    fn rays_through_the_canvas() {
+        let camera = Camera::new(201, 101, PI / 2.0);
+        let ray = camera.ray_for_pixel(100.5, 50.5);
+        assert_eq!(ray, Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, -1.0)));
+        let ray = camera.ray_for_pixel(0.5, 0.5);
+        assert!((ray.direction - Tuple::vector(0.66519, 0.33259, -0.66851)).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn rays_from_a_transformed_camera() {
+        let mut camera = Camera::new(201, 101, PI / 2.0);
+        camera.set_transform(Matrix::rotation_y(PI / 4.0) * Matrix::translation(0.0, -2.0, 5.0)).unwrap();
+        let ray = camera.ray_for_pixel(100.5, 50.5);
+        assert_eq!(ray.origin, Tuple::point(0.0, 2.0, -5.0));
+        let half = 2f64.sqrt() / 2.0;
+        assert!((ray.direction - Tuple::vector(half, 0.0, -half)).magnitude() < 1e-9);
+    }
+
+    #[test]
+    fn rendering_a_world() {
+        let world = default_world();
+        let mut camera = Camera::new(11, 11, PI / 2.0);
+        let view = Matrix::view_transform(Tuple::point(0.0, 0.0, -5.0), Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 1.0, 0.0));
+        camera.set_transform(view).unwrap();
+        let image = camera.render(&world, &Renderer::new(2), &Sampler::default(), 5);
+        assert_eq!(world.statistics().camera_rays, 121);
+        let pixel = image.pixel_at(5, 5);
+        let expected = Color::new(0.38066, 0.47583, 0.2855);
+        assert!((pixel.red - expected.red).abs() < 1e-4 && (pixel.green - expected.green).abs() < 1e-4);
+    }
+
+    #[test]
+    fn cancelling_a_camera_render() {
+        let world = default_world();
+        let mut camera = Camera::new(11, 11, PI / 2.0);
+        let view = Matrix::view_transform(Tuple::point(0.0, 0.0, -5.0), Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 1.0, 0.0));
+        camera.set_transform(view).unwrap();
+        let cancel = AtomicBool::new(false);
+        let shader = camera.shader(&world, Integrator::Whitted, 5);
+        let stop_after_six_rows = |p: Progress| {
+            if p.completed_rows == 6 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+        };
+        let image = Renderer::new(1).render_sampled_with_progress(11, 11, &Sampler::default(), shader, stop_after_six_rows, &cancel);
+        assert_eq!(world.statistics().camera_rays, 66);
+        assert!(image.pixel_at(5, 5).red > 0.3);
+        assert_eq!(image.pixel_at(5, 6), Color::new(0.0, 0.0, 0.0));
+    }
+}
@@ -1,79 +1,484 @@
-use std::{fs::File, io::Write};
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    slice::ChunksMut,
+};
 
-use super::color::Color;
+use super::{
+    color::{Color, Rgba},
+    error,
+    tone::ExportPipeline,
+};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PpmFormat {
+    Plain,
+    Binary,
+}
+
+pub(crate) fn quantize(channel: f64, maxval: u16) -> u16 {
+    (channel.clamp(0.0, 1.0) * maxval as f64).round() as u16
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImageError {
+    UnsupportedFormat(String),
+    Malformed(String),
+    Truncated(String),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::UnsupportedFormat(message) => write!(f, "unsupported image format: {}", message),
+            ImageError::Malformed(message) => write!(f, "malformed image: {}", message),
+            ImageError::Truncated(message) => write!(f, "truncated image: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+// Reads the whitespace-separated ASCII headers shared by the Netpbm-style formats.
+pub(crate) struct HeaderParser<'a> {
+    pub(crate) data: &'a [u8],
+    pub(crate) position: usize,
+}
+
+impl<'a> HeaderParser<'a> {
+    fn skip_whitespace_and_comments(&mut self) {
+        while let Some(&byte) = self.data.get(self.position) {
+            if byte == b'#' {
+                while self.position < self.data.len() && self.data[self.position] != b'\n' {
+                    self.position += 1;
+                }
+            } else if byte.is_ascii_whitespace() {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    pub(crate) fn token(&mut self, what: &str) -> Result<&'a [u8], ImageError> {
+        self.skip_whitespace_and_comments();
+        let start = self.position;
+        while self.position < self.data.len() && !self.data[self.position].is_ascii_whitespace() && self.data[self.position] != b'#' {
+            self.position += 1;
+        }
+        if start == self.position {
+            return Err(ImageError::Truncated(format!("expected {} at byte {}", what, start)));
+        }
+        Ok(&self.data[start..self.position])
+    }
+
+    pub(crate) fn number(&mut self, what: &str) -> Result<usize, ImageError> {
+        let start = self.position;
+        let token = self.token(what)?;
+        std::str::from_utf8(token)
+            .ok()
+            .filter(|text| text.bytes().all(|byte| byte.is_ascii_digit()))
+            .and_then(|text| text.parse().ok())
+            .ok_or_else(|| ImageError::Malformed(format!("invalid {} '{}' after byte {}", what, String::from_utf8_lossy(token), start)))
+    }
+
+    pub(crate) fn float(&mut self, what: &str) -> Result<f64, ImageError> {
+        let start = self.position;
+        let token = self.token(what)?;
+        std::str::from_utf8(token)
+            .ok()
+            .and_then(|text| text.parse().ok())
+            .ok_or_else(|| ImageError::Malformed(format!("invalid {} '{}' after byte {}", what, String::from_utf8_lossy(token), start)))
+    }
+
+    // Binary rasters start after exactly one whitespace byte following the header.
+    pub(crate) fn raster_start(&mut self) -> Result<(), ImageError> {
+        match self.data.get(self.position) {
+            Some(byte) if byte.is_ascii_whitespace() => {
+                self.position += 1;
+                Ok(())
+            }
+            Some(_) => Err(ImageError::Malformed("expected whitespace before raster".to_string())),
+            None => Err(ImageError::Truncated("missing raster".to_string())),
+        }
+    }
+
+    fn sample(&mut self, maxval: usize, index: usize, binary: bool) -> Result<f64, ImageError> {
+        let value = if binary {
+            let width = if maxval > 255 { 2 } else { 1 };
+            let bytes = self.data.get(self.position..self.position + width).ok_or_else(|| {
+                ImageError::Truncated(format!("raster ends after {} of its samples", index))
+            })?;
+            self.position += width;
+            bytes.iter().fold(0, |value, &byte| value << 8 | byte as usize)
+        } else {
+            let what = format!("sample {}", index);
+            match self.number(&what) {
+                Err(ImageError::Truncated(_)) => {
+                    return Err(ImageError::Truncated(format!("raster ends after {} of its samples", index)));
+                }
+                result => result?,
+            }
+        };
+        if value > maxval {
+            return Err(ImageError::Malformed(format!("sample {} is {}, above maxval {}", index, value, maxval)));
+        }
+        Ok(value as f64 / maxval as f64)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Canvas {
     pub width: usize,
     pub height: usize,
-    pub pixels: Vec<Vec<Color>>,
+    pub pixels: Vec<Color>,
+    // Per-pixel coverage in the same layout as `pixels`. Without it every pixel is
+    // opaque; with it the colours are premultiplied by their alpha.
+    pub alpha: Option<Vec<f64>>,
+    pub export: ExportPipeline,
+}
+
+// A read-only window onto part of a canvas, in the canvas's own pixel layout.
+pub struct Region<'a> {
+    canvas: &'a Canvas,
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl<'a> Region<'a> {
+    pub fn row(&self, y: usize) -> &'a [Color] {
+        assert!(y < self.height, "row {} outside region of height {}", y, self.height);
+        &self.canvas.row(self.y + y)[self.x..self.x + self.width]
+    }
+
+    pub fn rows(&self) -> impl DoubleEndedIterator<Item = &'a [Color]> + '_ {
+        (0..self.height).map(|y| self.row(y))
+    }
+
+    pub fn pixel_at(&self, x: usize, y: usize) -> Color {
+        self.row(y)[x]
+    }
+
+    pub fn to_canvas(&self) -> Canvas {
+        let mut canvas = Canvas::new(self.width, self.height);
+        canvas.export = self.canvas.export;
+        for (target, source) in canvas.rows_mut().zip(self.rows()) {
+            target.copy_from_slice(source);
+        }
+        if let Some(alpha) = &self.canvas.alpha {
+            let start = |y: usize| (self.y + y) * self.canvas.width + self.x;
+            canvas.alpha = Some((0..self.height).flat_map(|y| alpha[start(y)..start(y) + self.width].iter().copied()).collect());
+        }
+        canvas
+    }
+}
+
+// A mutable window onto part of a canvas. Regions handed out together by
+// Canvas::tiles_mut never overlap, so each can be filled from its own thread.
+pub struct RegionMut<'a> {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    rows: Vec<&'a mut [Color]>,
+}
+
+impl<'a> RegionMut<'a> {
+    pub fn row_mut(&mut self, y: usize) -> &mut [Color] {
+        self.rows[y]
+    }
+
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [Color]> + use<'_, 'a> {
+        self.rows.iter_mut().map(|row| &mut **row)
+    }
+
+    pub fn pixel_at(&self, x: usize, y: usize) -> Color {
+        self.rows[y][x]
+    }
+
+    pub fn write_pixel(&mut self, x: usize, y: usize, color: Color) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        self.rows[y][x] = color;
+    }
 }
 
 impl Canvas {
     pub fn new(width: usize, height: usize) -> Self {
-        let mut pixels = Vec::new();
-        for _ in 0..height {
-            let mut row = Vec::new();
-            for _ in 0..width {
-                row.push(Color::new(0.0, 0.0, 0.0));
-            }
-            pixels.push(row);
+        let pixels = vec![Color::new(0.0, 0.0, 0.0); width * height];
+        Self { width, height, pixels, alpha: None, export: ExportPipeline::default() }
+    }
+
+    pub fn alpha_at(&self, x: usize, y: usize) -> f64 {
+        assert!(x < self.width && y < self.height, "pixel ({}, {}) outside {}x{} canvas", x, y, self.width, self.height);
+        self.alpha.as_ref().map_or(1.0, |alpha| alpha[y * self.width + x])
+    }
+
+    // Adds an opaque alpha plane the first time any pixel is given coverage.
+    pub fn write_alpha(&mut self, x: usize, y: usize, alpha: f64) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let plane = self.alpha.get_or_insert_with(|| vec![1.0; self.pixels.len()]);
+        plane[y * self.width + x] = alpha;
+    }
+
+    pub fn rgba_at(&self, x: usize, y: usize) -> Rgba {
+        Rgba::new(self.pixel_at(x, y), self.alpha_at(x, y))
+    }
+
+    // Porter-Duff "over": this canvas composited onto `background`.
+    pub fn over(&self, background: &Canvas) -> error::Result<Canvas> {
+        if (self.width, self.height) != (background.width, background.height) {
+            return Err(error::Error::CanvasSizeMismatch {
+                expected: (self.width, self.height),
+                actual: (background.width, background.height),
+            });
+        }
+        let mut result = Canvas::new(self.width, self.height);
+        result.export = self.export;
+        if self.alpha.is_none() {
+            result.pixels.copy_from_slice(&self.pixels);
+            return Ok(result);
+        }
+        let mut alpha = Vec::with_capacity(self.pixels.len());
+        for (index, pixel) in result.pixels.iter_mut().enumerate() {
+            let (x, y) = (index % self.width, index / self.width);
+            let composited = self.rgba_at(x, y).over(background.rgba_at(x, y));
+            *pixel = composited.color;
+            alpha.push(composited.alpha);
+        }
+        if background.alpha.is_some() {
+            result.alpha = Some(alpha);
+        }
+        Ok(result)
+    }
+
+    // Composites onto a solid background, dropping the alpha plane.
+    pub fn flatten(&self, background: Color) -> Canvas {
+        let mut result = Canvas::new(self.width, self.height);
+        result.export = self.export;
+        for (index, pixel) in result.pixels.iter_mut().enumerate() {
+            let (x, y) = (index % self.width, index / self.width);
+            *pixel = self.rgba_at(x, y).over(Rgba::opaque(background)).color;
         }
-        Self { width, height, pixels }
+        result
     }
 
     pub fn write_pixel(&mut self, x: usize, y: usize, color: Color) {
         if x >= self.width || y >= self.height {
             return;
         }
-        self.pixels[y][x] = color;
+        self.pixels[y * self.width + x] = color;
     }
 
     pub fn pixel_at(&self, x: usize, y: usize) -> Color {
-        self.pixels[y][x]
+        self.row(y)[x]
+    }
+
+    pub fn row(&self, y: usize) -> &[Color] {
+        &self.pixels[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> &mut [Color] {
+        &mut self.pixels[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn rows(&self) -> impl DoubleEndedIterator<Item = &[Color]> {
+        (0..self.height).map(|y| self.row(y))
+    }
+
+    pub fn rows_mut(&mut self) -> ChunksMut<'_, Color> {
+        self.pixels.chunks_mut(self.width.max(1))
+    }
+
+    pub fn enumerate_pixels(&self) -> impl Iterator<Item = (usize, usize, Color)> + '_ {
+        self.pixels.iter().enumerate().map(|(index, pixel)| (index % self.width, index / self.width, *pixel))
+    }
+
+    pub fn enumerate_pixels_mut(&mut self) -> impl Iterator<Item = (usize, usize, &mut Color)> {
+        let width = self.width;
+        self.pixels.iter_mut().enumerate().map(move |(index, pixel)| (index % width, index / width, pixel))
+    }
+
+    // Regions are clipped to the canvas.
+    pub fn region(&self, x: usize, y: usize, width: usize, height: usize) -> Region<'_> {
+        let (x, y) = (x.min(self.width), y.min(self.height));
+        Region { canvas: self, x, y, width: width.min(self.width - x), height: height.min(self.height - y) }
+    }
+
+    pub fn region_mut(&mut self, x: usize, y: usize, width: usize, height: usize) -> RegionMut<'_> {
+        let (x, y) = (x.min(self.width), y.min(self.height));
+        let (width, height) = (width.min(self.width - x), height.min(self.height - y));
+        let rows = self.rows_mut().skip(y).take(height).map(|row| &mut row[x..x + width]).collect();
+        RegionMut { x, y, width, height, rows }
+    }
+
+    // Splits the canvas into disjoint tiles, row-major; tiles on the right and bottom
+    // edges are smaller when the canvas does not divide evenly.
+    pub fn tiles_mut(&mut self, tile_width: usize, tile_height: usize) -> Vec<RegionMut<'_>> {
+        let (tile_width, tile_height) = (tile_width.max(1), tile_height.max(1));
+        let width = self.width;
+        let mut tiles = Vec::new();
+        if width == 0 {
+            return tiles;
+        }
+        for (band, band_pixels) in self.pixels.chunks_mut(width * tile_height).enumerate() {
+            let first = tiles.len();
+            for (row_in_band, row) in band_pixels.chunks_mut(width).enumerate() {
+                for (column, part) in row.chunks_mut(tile_width).enumerate() {
+                    if row_in_band == 0 {
+                        tiles.push(RegionMut {
+                            x: column * tile_width,
+                            y: band * tile_height,
+                            width: part.len(),
+                            height: 0,
+                            rows: Vec::new(),
+                        });
+                    }
+                    let tile = &mut tiles[first + column];
+                    tile.height += 1;
+                    tile.rows.push(part);
+                }
+            }
+        }
+        tiles
     }
 
     pub fn canvas_to_ppm(&self) -> String {
-        let mut ppm = format!("P3\n{} {}\n255\n", self.width, self.height);
-        let mut line_length = 0;
-        for row in self.pixels.iter() {
-            for pixel in row.iter() {
-                let r = (pixel.red * 255.0).round() as u8;
-                let g = (pixel.green * 255.0).round() as u8;
-                let b = (pixel.blue * 255.0).round() as u8;
-                let line = format!("{} {} {} ", r, g, b);
-                line_length += line.len();
-                if line_length > 70 {
-                    ppm.push_str("\n");
-                    line_length = line.len();
+        let mut ppm = Vec::new();
+        self.write_ppm(&mut ppm, PpmFormat::Plain, 255).unwrap();
+        String::from_utf8(ppm).unwrap()
+    }
+
+    // Streams the image one row at a time, passing each pixel through the canvas's
+    // export pipeline before quantizing it. Plain (P3) output keeps lines under 70
+    // characters; binary (P6) output uses two big-endian bytes per sample when
+    // maxval is above 255.
+    pub fn write_ppm<W: Write>(&self, mut writer: W, format: PpmFormat, maxval: u16) -> io::Result<()> {
+        let maxval = maxval.max(1);
+        let magic = match format {
+            PpmFormat::Plain => "P3",
+            PpmFormat::Binary => "P6",
+        };
+        write!(writer, "{}\n{} {}\n{}\n", magic, self.width, self.height, maxval)?;
+        match format {
+            PpmFormat::Plain => {
+                let mut line_length = 0;
+                let mut text = String::new();
+                for row in self.rows() {
+                    for pixel in row.iter() {
+                        let pixel = self.export.apply(*pixel);
+                        let r = quantize(pixel.red, maxval);
+                        let g = quantize(pixel.green, maxval);
+                        let b = quantize(pixel.blue, maxval);
+                        let line = format!("{} {} {} ", r, g, b);
+                        line_length += line.len();
+                        if line_length > 70 {
+                            text.push('\n');
+                            line_length = line.len();
+                        }
+                        text.push_str(&line);
+                    }
+                    text.push('\n');
+                    line_length = 0;
+                    writer.write_all(text.as_bytes())?;
+                    text.clear();
                 }
-                ppm.push_str(&line);
             }
-            ppm.push_str("\n");
+            PpmFormat::Binary => {
+                let mut bytes = Vec::with_capacity(self.width * 6);
+                for row in self.rows() {
+                    for pixel in row.iter() {
+                        let pixel = self.export.apply(*pixel);
+                        for channel in [pixel.red, pixel.green, pixel.blue] {
+                            let value = quantize(channel, maxval);
+                            if maxval > 255 {
+                                bytes.extend_from_slice(&value.to_be_bytes());
+                            } else {
+                                bytes.push(value as u8);
+                            }
+                        }
+                    }
+                    writer.write_all(&bytes)?;
+                    bytes.clear();
+                }
+            }
+        }
+        writer.flush()
+    }
+
+    // Reads plain (P3) and binary (P6) PPM data of any maxval, scaling samples into
+    // 0.0..=1.0. The samples are kept as stored, so the canvas gets the linear export
+    // pipeline and writes them back unchanged.
+    pub fn from_ppm(data: &[u8]) -> Result<Self, ImageError> {
+        let mut parser = HeaderParser { data, position: 0 };
+        let binary = match parser.token("magic number")? {
+            b"P3" => false,
+            b"P6" => true,
+            magic => return Err(ImageError::UnsupportedFormat(format!("PPM magic number '{}'", String::from_utf8_lossy(magic)))),
+        };
+        let width = parser.number("width")?;
+        let height = parser.number("height")?;
+        let maxval = parser.number("maxval")?;
+        if maxval == 0 || maxval > 65535 {
+            return Err(ImageError::Malformed(format!("maxval {} is outside 1..=65535", maxval)));
+        }
+        if binary {
+            parser.raster_start()?;
         }
-        ppm
+        // Every sample takes at least one byte, so reject impossible sizes before allocating.
+        let samples = width.checked_mul(height).and_then(|pixels| pixels.checked_mul(3));
+        if samples.is_none_or(|samples| samples > data.len() - parser.position) {
+            return Err(ImageError::Truncated(format!("{}x{} image does not fit in {} bytes", width, height, data.len())));
+        }
+
+        let mut canvas = Canvas::new(width, height);
+        let mut index = 0;
+        for y in 0..height {
+            for x in 0..width {
+                let red = parser.sample(maxval, index, binary)?;
+                let green = parser.sample(maxval, index + 1, binary)?;
+                let blue = parser.sample(maxval, index + 2, binary)?;
+                canvas.write_pixel(x, y, Color::new(red, green, blue));
+                index += 3;
+            }
+        }
+        canvas.export = ExportPipeline::linear();
+        Ok(canvas)
+    }
+
+    pub fn canvas_to_file(&self, filename: &str) -> error::Result<()> {
+        let file = File::create(filename)?;
+        self.write_ppm(BufWriter::new(file), PpmFormat::Plain, 255)?;
+        Ok(())
     }
 
-    pub fn canvas_to_file(&self, filename: &str) {
-        let ppm = self.canvas_to_ppm();
-        let mut file = File::create(filename).unwrap();
-        file.write_all(ppm.as_bytes()).unwrap();
+    pub fn ppm_from_file(filename: &str) -> error::Result<Self> {
+        Ok(Self::from_ppm(&fs::read(filename)?)?)
     }
-        
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::features::{
+        error::Error,
+        tone::{Gamma, ToneMapping},
+    };
 
     #[test]
     fn creating_canvas() {
         let c = Canvas::new(10, 20);
         assert_eq!(c.width, 10);
         assert_eq!(c.height, 20);
-        for row in c.pixels {
-            for pixel in row {
-                assert_eq!(pixel, Color::new(0.0, 0.0, 0.0));
-            }
+        assert_eq!(c.pixels.len(), 200);
+        for pixel in c.pixels {
+            assert_eq!(pixel, Color::new(0.0, 0.0, 0.0));
         }
     }
 
@@ -85,6 +490,85 @@ mod tests {
         assert_eq!(c.pixel_at(2, 3), red);
     }
 
+    #[test]
+    fn writing_outside_canvas_is_ignored() {
+        let mut c = Canvas::new(3, 2);
+        c.write_pixel(3, 0, Color::new(1.0, 0.0, 0.0));
+        c.write_pixel(0, 2, Color::new(1.0, 0.0, 0.0));
+        assert!(c.pixels.iter().all(|pixel| *pixel == Color::new(0.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn reading_outside_row_panics() {
+        let c = Canvas::new(3, 2);
+        c.pixel_at(3, 0);
+    }
+
+    #[test]
+    fn accessing_rows() {
+        let mut c = Canvas::new(3, 2);
+        c.row_mut(1)[2] = Color::new(0.0, 1.0, 0.0);
+        assert_eq!(c.row(1), &[Color::new(0.0, 0.0, 0.0), Color::new(0.0, 0.0, 0.0), Color::new(0.0, 1.0, 0.0)]);
+        for (y, row) in c.rows_mut().enumerate() {
+            row[0] = Color::new(y as f64, 0.0, 0.0);
+        }
+        assert_eq!(c.pixel_at(0, 1), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(c.rows().count(), 2);
+    }
+
+    #[test]
+    fn enumerating_pixels() {
+        let mut c = Canvas::new(3, 2);
+        for (x, y, pixel) in c.enumerate_pixels_mut() {
+            *pixel = Color::new(x as f64, y as f64, 0.0);
+        }
+        let pixels: Vec<(usize, usize, Color)> = c.enumerate_pixels().collect();
+        assert_eq!(pixels.len(), 6);
+        assert_eq!(pixels[4], (1, 1, Color::new(1.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn regions_view_part_of_canvas() {
+        let mut c = Canvas::new(4, 3);
+        c.write_pixel(2, 1, Color::new(1.0, 0.0, 0.0));
+        let region = c.region(1, 1, 2, 5);
+        assert_eq!((region.width, region.height), (2, 2));
+        assert_eq!(region.pixel_at(1, 0), Color::new(1.0, 0.0, 0.0));
+        let copy = region.to_canvas();
+        assert_eq!(copy.pixel_at(1, 0), Color::new(1.0, 0.0, 0.0));
+
+        let mut region = c.region_mut(2, 0, 2, 2);
+        region.write_pixel(1, 1, Color::new(0.0, 0.0, 1.0));
+        region.write_pixel(2, 1, Color::new(0.0, 0.0, 1.0));
+        assert_eq!(c.pixel_at(3, 1), Color::new(0.0, 0.0, 1.0));
+        assert_eq!(c.pixels.iter().filter(|pixel| pixel.blue == 1.0).count(), 1);
+    }
+
+    #[test]
+    fn tiles_cover_canvas_without_overlap() {
+        let mut c = Canvas::new(5, 3);
+        let mut tiles = c.tiles_mut(2, 2);
+        assert_eq!(tiles.len(), 6);
+        assert_eq!((tiles[2].x, tiles[2].y, tiles[2].width, tiles[2].height), (4, 0, 1, 2));
+        assert_eq!((tiles[5].x, tiles[5].y, tiles[5].width, tiles[5].height), (4, 2, 1, 1));
+        std::thread::scope(|scope| {
+            for tile in tiles.iter_mut() {
+                scope.spawn(move || {
+                    let (x0, y0) = (tile.x, tile.y);
+                    for (y, row) in tile.rows_mut().enumerate() {
+                        for (x, pixel) in row.iter_mut().enumerate() {
+                            *pixel = *pixel + Color::new((x0 + x) as f64, (y0 + y) as f64, 1.0);
+                        }
+                    }
+                });
+            }
+        });
+        for (x, y, pixel) in c.enumerate_pixels() {
+            assert_eq!(pixel, Color::new(x as f64, y as f64, 1.0));
+        }
+    }
+
     #[test]
     fn constructing_ppm_header() {
         let c = Canvas::new(5, 3);
@@ -94,4 +578,152 @@ mod tests {
         assert_eq!(lines[1], "5 3");
         assert_eq!(lines[2], "255");
     }
+
+    #[test]
+    fn constructing_ppm_pixel_data() {
+        let mut c = Canvas::new(5, 3);
+        c.write_pixel(0, 0, Color::new(1.5, 0.0, 0.0));
+        c.write_pixel(2, 1, Color::new(0.0, 0.5, 0.0));
+        c.write_pixel(4, 2, Color::new(-0.5, 0.0, 1.0));
+        let ppm = c.canvas_to_ppm();
+        let lines: Vec<&str> = ppm.split("\n").collect();
+        assert_eq!(lines[3], "255 0 0 0 0 0 0 0 0 0 0 0 0 0 0 ");
+        assert_eq!(lines[4], "0 0 0 0 0 0 0 188 0 0 0 0 0 0 0 ");
+        assert_eq!(lines[5], "0 0 0 0 0 0 0 0 0 0 0 0 0 0 255 ");
+    }
+
+    #[test]
+    fn ppm_lines_stay_under_70_characters() {
+        let mut c = Canvas::new(10, 2);
+        for y in 0..2 {
+            for x in 0..10 {
+                c.write_pixel(x, y, Color::new(1.0, 0.8, 0.6));
+            }
+        }
+        let ppm = c.canvas_to_ppm();
+        assert!(ppm.lines().all(|line| line.len() <= 70));
+        assert!(ppm.ends_with('\n'));
+    }
+
+    #[test]
+    fn writing_binary_ppm() {
+        let mut c = Canvas::new(2, 1);
+        c.write_pixel(0, 0, Color::new(1.0, 0.5, 0.0));
+        c.write_pixel(1, 0, Color::new(0.0, 0.0, 1.0));
+        let mut ppm = Vec::new();
+        c.write_ppm(&mut ppm, PpmFormat::Binary, 255).unwrap();
+        let mut expected = b"P6\n2 1\n255\n".to_vec();
+        expected.extend_from_slice(&[255, 188, 0, 0, 0, 255]);
+        assert_eq!(ppm, expected);
+    }
+
+    #[test]
+    fn writing_16_bit_binary_ppm() {
+        let mut c = Canvas::new(1, 1);
+        c.write_pixel(0, 0, Color::new(1.0, 0.5, 0.0));
+        let mut ppm = Vec::new();
+        c.write_ppm(&mut ppm, PpmFormat::Binary, 65535).unwrap();
+        let mut expected = b"P6\n1 1\n65535\n".to_vec();
+        expected.extend_from_slice(&[0xff, 0xff, 0xbc, 0x40, 0x00, 0x00]);
+        assert_eq!(ppm, expected);
+    }
+
+    #[test]
+    fn reading_plain_ppm_with_comments() {
+        let ppm = b"P3\n# a comment\n2 1 # trailing\n  10\n10 5 0\n\n0 0   10\n";
+        let c = Canvas::from_ppm(ppm).unwrap();
+        assert_eq!(c.width, 2);
+        assert_eq!(c.height, 1);
+        assert_eq!(c.pixel_at(0, 0), Color::new(1.0, 0.5, 0.0));
+        assert_eq!(c.pixel_at(1, 0), Color::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn ppm_round_trips_through_binary_and_plain() {
+        let mut c = Canvas::new(3, 2);
+        c.write_pixel(0, 0, Color::new(1.0, 0.0, 0.2));
+        c.write_pixel(2, 1, Color::new(0.4, 0.6, 0.8));
+        for (format, maxval) in [(PpmFormat::Plain, 255), (PpmFormat::Binary, 255), (PpmFormat::Binary, 65535)] {
+            let mut ppm = Vec::new();
+            c.write_ppm(&mut ppm, format, maxval).unwrap();
+            let read = Canvas::from_ppm(&ppm).unwrap();
+            let mut rewritten = Vec::new();
+            read.write_ppm(&mut rewritten, format, maxval).unwrap();
+            assert_eq!(rewritten, ppm);
+            for y in 0..2 {
+                for x in 0..3 {
+                    let expected = c.export.apply(c.pixel_at(x, y));
+                    let actual = read.pixel_at(x, y);
+                    assert!((expected.red - actual.red).abs() < 0.5 / maxval as f64);
+                    assert!((expected.green - actual.green).abs() < 0.5 / maxval as f64);
+                    assert!((expected.blue - actual.blue).abs() < 0.5 / maxval as f64);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reading_unsupported_ppm() {
+        let error = Canvas::from_ppm(b"P5\n1 1\n255\n\0").err().unwrap();
+        assert_eq!(error, ImageError::UnsupportedFormat("PPM magic number 'P5'".to_string()));
+    }
+
+    #[test]
+    fn reading_truncated_ppm() {
+        assert!(matches!(Canvas::from_ppm(b"P3\n2 1\n"), Err(ImageError::Truncated(_))));
+        assert!(matches!(Canvas::from_ppm(b"P3\n2 1\n255\n1 2 3 4"), Err(ImageError::Truncated(_))));
+        assert!(matches!(Canvas::from_ppm(b"P6\n2 1\n255\n\x01\x02\x03"), Err(ImageError::Truncated(_))));
+        assert!(matches!(Canvas::from_ppm(b"P6\n100000 100000\n255\n\0"), Err(ImageError::Truncated(_))));
+    }
+
+    #[test]
+    fn reading_malformed_ppm() {
+        assert!(matches!(Canvas::from_ppm(b"P3\nwide 1\n255\n"), Err(ImageError::Malformed(_))));
+        assert!(matches!(Canvas::from_ppm(b"P3\n1 1\n0\n0 0 0"), Err(ImageError::Malformed(_))));
+        assert!(matches!(Canvas::from_ppm(b"P3\n1 1\n15\n0 16 0"), Err(ImageError::Malformed(_))));
+    }
+
+    #[test]
+    fn ppm_output_goes_through_export_pipeline() {
+        let mut c = Canvas::new(1, 1);
+        c.write_pixel(0, 0, Color::new(3.0, 1.0, 0.0));
+        c.export = ExportPipeline::new(0.0, ToneMapping::Reinhard, Gamma::Linear);
+        let ppm = c.canvas_to_ppm();
+        let lines: Vec<&str> = ppm.split("\n").collect();
+        assert_eq!(lines[3], "191 128 0 ");
+    }
+
+    #[test]
+    fn compositing_canvases() {
+        let mut foreground = Canvas::new(2, 1);
+        foreground.write_pixel(0, 0, Color::new(0.5, 0.0, 0.0));
+        foreground.write_alpha(0, 0, 0.5);
+        foreground.write_alpha(1, 0, 0.0);
+        let mut background = Canvas::new(2, 1);
+        background.pixels = vec![Color::new(0.0, 0.0, 1.0); 2];
+
+        let composited = foreground.over(&background).unwrap();
+        assert_eq!(composited.pixel_at(0, 0), Color::new(0.5, 0.0, 0.5));
+        assert_eq!(composited.pixel_at(1, 0), Color::new(0.0, 0.0, 1.0));
+        assert!(composited.alpha.is_none());
+        assert_eq!(foreground.flatten(Color::new(0.0, 0.0, 1.0)).pixels, composited.pixels);
+        assert!(matches!(foreground.over(&Canvas::new(1, 1)), Err(Error::CanvasSizeMismatch { .. })));
+    }
+
+    #[test]
+    fn canvases_are_opaque_until_given_alpha() {
+        let mut c = Canvas::new(2, 2);
+        assert!(c.alpha.is_none());
+        assert_eq!(c.alpha_at(1, 1), 1.0);
+        c.write_alpha(1, 1, 0.25);
+        assert_eq!(c.alpha_at(0, 0), 1.0);
+        assert_eq!(c.alpha_at(1, 1), 0.25);
+    }
+
+    #[test]
+    fn file_errors_are_reported() {
+        let c = Canvas::new(1, 1);
+        assert!(matches!(c.canvas_to_file("/nonexistent-directory/out.ppm"), Err(Error::Io(_))));
+        assert!(matches!(Canvas::ppm_from_file("/nonexistent-directory/in.ppm"), Err(Error::Io(_))));
+    }
 }
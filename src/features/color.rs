@@ -75,6 +75,55 @@ impl Mul for Color {
     }
 }
 
+// A colour with coverage, stored premultiplied: `color` already includes the alpha
+// factor, so averaging samples and compositing are plain sums.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rgba {
+    pub color: Color,
+    pub alpha: f64,
+}
+
+impl Rgba {
+    pub fn new(color: Color, alpha: f64) -> Self {
+        Self { color, alpha }
+    }
+
+    pub fn transparent() -> Self {
+        Self::new(Color::new(0.0, 0.0, 0.0), 0.0)
+    }
+
+    pub fn opaque(color: Color) -> Self {
+        Self::new(color, 1.0)
+    }
+
+    // Porter-Duff "over".
+    pub fn over(self, background: Self) -> Self {
+        Self::new(self.color + background.color * (1.0 - self.alpha), self.alpha + background.alpha * (1.0 - self.alpha))
+    }
+
+    pub fn unpremultiplied(&self) -> Color {
+        if self.alpha > 0.0 {
+            self.color * (1.0 / self.alpha)
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        }
+    }
+}
+
+impl Add for Rgba {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self::new(self.color + other.color, self.alpha + other.alpha)
+    }
+}
+
+impl Mul<f64> for Rgba {
+    type Output = Self;
+    fn mul(self, scalar: f64) -> Self {
+        Self::new(self.color * scalar, self.alpha * scalar)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -113,4 +162,13 @@ mod tests {
         let c2 = Color::new(0.9, 1.0, 0.1);
         assert_eq!(c1 * c2, Color::new(0.9, 0.2, 0.04));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn compositing_over() {
+        let foreground = Rgba::new(Color::new(0.5, 0.0, 0.0), 0.5);
+        let background = Rgba::opaque(Color::new(0.0, 0.0, 1.0));
+        assert_eq!(foreground.over(background), Rgba::new(Color::new(0.5, 0.0, 0.5), 1.0));
+        assert_eq!(Rgba::transparent().over(background), background);
+        assert_eq!(foreground.unpremultiplied(), Color::new(1.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,260 @@
+use std::{
+    env,
+    fs::{self, File},
+    io::BufWriter,
+    path::Path,
+};
+
+use super::{
+    canvas::{Canvas, PpmFormat},
+    color::Color,
+    error::{Error, Result},
+    tone::ExportPipeline,
+};
+
+#[derive(Debug)]
+pub struct ImageDiff {
+    pub error_image: Canvas,
+    pub max_error: f64,
+    pub rmse: f64,
+    pub psnr: f64,
+    pub over_threshold: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    pub pixel_threshold: f64,
+    pub max_pixels_over: usize,
+    pub max_rmse: f64,
+}
+
+impl Tolerance {
+    pub fn new(pixel_threshold: f64, max_pixels_over: usize, max_rmse: f64) -> Self {
+        Self { pixel_threshold, max_pixels_over, max_rmse }
+    }
+
+    pub fn accepts(&self, diff: &ImageDiff) -> bool {
+        diff.over_threshold <= self.max_pixels_over && diff.rmse <= self.max_rmse
+    }
+}
+
+// Allows differences of up to one 8-bit step, too small to show in PNG or 8-bit PPM
+// output, and fails on anything visible.
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self::new(1.0 / 255.0, 0, 1.0 / 255.0)
+    }
+}
+
+impl Canvas {
+    // Compares this canvas against `reference` channel by channel, alpha included
+    // when either has an alpha plane (a canvas without one is opaque). The error image
+    // holds the absolute difference of each colour channel, raised to the difference
+    // in alpha where that is larger; a pixel counts as over the threshold when any of
+    // its channels is.
+    pub fn diff(&self, reference: &Canvas, threshold: f64) -> Result<ImageDiff> {
+        if (self.width, self.height) != (reference.width, reference.height) {
+            return Err(Error::CanvasSizeMismatch { expected: (reference.width, reference.height), actual: (self.width, self.height) });
+        }
+        let mut error_image = Canvas::new(self.width, self.height);
+        error_image.export = ExportPipeline::linear();
+        let alpha = |canvas: &Canvas, index: usize| canvas.alpha.as_ref().map_or(1.0, |alpha| alpha[index]);
+        let channels = if self.alpha.is_some() || reference.alpha.is_some() { 4 } else { 3 };
+        let (mut max_error, mut squared_sum, mut over_threshold) = (0.0f64, 0.0, 0);
+        for (index, ((error, a), b)) in error_image.pixels.iter_mut().zip(self.pixels.iter()).zip(reference.pixels.iter()).enumerate() {
+            let coverage = (alpha(self, index) - alpha(reference, index)).abs();
+            let (red, green, blue) = ((a.red - b.red).abs(), (a.green - b.green).abs(), (a.blue - b.blue).abs());
+            *error = Color::new(red.max(coverage), green.max(coverage), blue.max(coverage));
+            let largest = red.max(green).max(blue).max(coverage);
+            max_error = max_error.max(largest);
+            squared_sum += red * red + green * green + blue * blue + coverage * coverage;
+            if largest > threshold {
+                over_threshold += 1;
+            }
+        }
+        let samples = (self.pixels.len() * channels).max(1) as f64;
+        let rmse = (squared_sum / samples).sqrt();
+        let psnr = if rmse == 0.0 { f64::INFINITY } else { -20.0 * rmse.log10() };
+        Ok(ImageDiff { error_image, max_error, rmse, psnr, over_threshold })
+    }
+
+    // The pixel values the 8/16-bit writers would quantize, i.e. after the export
+    // pipeline, with the alpha plane carried over. They are display values already,
+    // so the result exports them as they are.
+    pub fn display_canvas(&self) -> Canvas {
+        let mut display = Canvas::new(self.width, self.height);
+        display.export = ExportPipeline::linear();
+        display.alpha = self.alpha.clone();
+        for (target, source) in display.pixels.iter_mut().zip(self.pixels.iter()) {
+            *target = self.export.apply(*source);
+        }
+        display
+    }
+}
+
+// PPM has no alpha channel, so a canvas's alpha plane goes to a grey image next to
+// it, `<name>.alpha.ppm`.
+fn alpha_path(path: &Path) -> std::path::PathBuf {
+    path.with_extension("alpha.ppm")
+}
+
+fn write_reference(canvas: &Canvas, path: &Path) -> Result<()> {
+    canvas.write_ppm(BufWriter::new(File::create(path)?), PpmFormat::Binary, 65535)?;
+    match &canvas.alpha {
+        Some(alpha) => {
+            let mut grey = Canvas::new(canvas.width, canvas.height);
+            grey.export = ExportPipeline::linear();
+            grey.pixels = alpha.iter().map(|&a| Color::new(a, a, a)).collect();
+            grey.write_ppm(BufWriter::new(File::create(alpha_path(path))?), PpmFormat::Binary, 65535)?;
+        }
+        None if alpha_path(path).is_file() => fs::remove_file(alpha_path(path))?,
+        None => {}
+    }
+    Ok(())
+}
+
+fn read_reference(path: &Path) -> Result<Canvas> {
+    let mut canvas = Canvas::ppm_from_file(&path.to_string_lossy())?;
+    if alpha_path(path).is_file() {
+        let grey = Canvas::ppm_from_file(&alpha_path(path).to_string_lossy())?;
+        canvas.alpha = Some(grey.pixels.iter().map(|pixel| pixel.red).collect());
+    }
+    Ok(canvas)
+}
+
+// Golden-image check for tests: compares the displayed form of `canvas`, coverage
+// included, against the PPM at `reference` and panics with the diff metrics when it
+// is out of tolerance, leaving `<reference>.actual.ppm` and `<reference>.diff.ppm`
+// next to it for inspection. Setting UPDATE_REFERENCE_IMAGES=1 (re)writes the
+// reference instead.
+pub fn assert_matches_reference(canvas: &Canvas, reference: impl AsRef<Path>, tolerance: Tolerance) {
+    let reference = reference.as_ref();
+    let display = canvas.display_canvas();
+    if env::var_os("UPDATE_REFERENCE_IMAGES").is_some_and(|value| value == "1") {
+        write_reference(&display, reference).unwrap_or_else(|error| panic!("cannot write {}: {}", reference.display(), error));
+        return;
+    }
+
+    let expected = read_reference(reference)
+        .unwrap_or_else(|error| panic!("cannot load reference {}: {}", reference.display(), error));
+    let diff = display.diff(&expected, tolerance.pixel_threshold).unwrap_or_else(|error| panic!("{}: {}", reference.display(), error));
+    if tolerance.accepts(&diff) {
+        return;
+    }
+
+    let sibling = |suffix: &str| reference.with_extension(format!("{}.ppm", suffix));
+    let _ = write_reference(&display, &sibling("actual"));
+    let _ = write_reference(&diff.error_image, &sibling("diff"));
+    panic!(
+        "{} differs from render: {} pixels over {} (allowed {}), RMSE {:.6} (allowed {:.6}), max error {:.6}, PSNR {:.2} dB",
+        reference.display(),
+        diff.over_threshold,
+        tolerance.pixel_threshold,
+        tolerance.max_pixels_over,
+        diff.rmse,
+        tolerance.max_rmse,
+        diff.max_error,
+        diff.psnr,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::util::almost_equal;
+
+    fn gradient() -> Canvas {
+        let mut c = Canvas::new(8, 4);
+        for (x, y, pixel) in c.enumerate_pixels_mut() {
+            *pixel = Color::new(x as f64 / 8.0, y as f64 / 4.0, 0.5);
+        }
+        c
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        env::temp_dir().join(format!("ray-tracer-challenge-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn identical_canvases_have_no_error() {
+        let diff = gradient().diff(&gradient(), 0.0).unwrap();
+        assert_eq!(diff.max_error, 0.0);
+        assert_eq!(diff.rmse, 0.0);
+        assert_eq!(diff.psnr, f64::INFINITY);
+        assert_eq!(diff.over_threshold, 0);
+    }
+
+    #[test]
+    fn diff_metrics() {
+        let a = Canvas::new(2, 1);
+        let mut b = Canvas::new(2, 1);
+        b.write_pixel(1, 0, Color::new(0.0, 0.6, 0.0));
+        let diff = a.diff(&b, 0.5).unwrap();
+        assert_eq!(diff.error_image.pixel_at(1, 0), Color::new(0.0, 0.6, 0.0));
+        assert!(almost_equal(diff.max_error, 0.6));
+        assert!(almost_equal(diff.rmse, (0.36f64 / 6.0).sqrt()));
+        assert!(almost_equal(diff.psnr, -20.0 * (0.06f64).sqrt().log10()));
+        assert_eq!(diff.over_threshold, 1);
+        assert_eq!(a.diff(&b, 0.6).unwrap().over_threshold, 0);
+    }
+
+    #[test]
+    fn diffing_different_sizes_fails() {
+        let result = Canvas::new(2, 1).diff(&Canvas::new(1, 2), 0.0);
+        assert!(matches!(result, Err(Error::CanvasSizeMismatch { expected: (1, 2), actual: (2, 1) })));
+    }
+
+    #[test]
+    fn render_matches_stored_reference() {
+        let path = temp_path("reference.ppm");
+        write_reference(&gradient(), &path).unwrap();
+        assert_matches_reference(&gradient(), &path, Tolerance::default());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn coverage_is_compared_too() {
+        let mut transparent = Canvas::new(2, 1);
+        transparent.write_alpha(1, 0, 0.0);
+        let diff = transparent.diff(&Canvas::new(2, 1), 0.5).unwrap();
+        assert_eq!(diff.error_image.pixel_at(1, 0), Color::new(1.0, 1.0, 1.0));
+        assert_eq!(diff.over_threshold, 1);
+        assert!(almost_equal(diff.rmse, (1.0f64 / 8.0).sqrt()));
+        assert_eq!(transparent.display_canvas().alpha, transparent.alpha);
+
+        let path = temp_path("coverage.ppm");
+        write_reference(&transparent, &path).unwrap();
+        assert_matches_reference(&transparent, &path, Tolerance::default());
+        let result = std::panic::catch_unwind(|| assert_matches_reference(&Canvas::new(2, 1), &path, Tolerance::default()));
+        assert!(result.is_err());
+        for file in [path.clone(), alpha_path(&path), path.with_extension("actual.ppm"), path.with_extension("diff.ppm")] {
+            let _ = std::fs::remove_file(file);
+        }
+    }
+
+    #[test]
+    fn renders_of_the_wrong_size_report_the_reference_size_as_expected() {
+        let path = temp_path("size.ppm");
+        write_reference(&gradient(), &path).unwrap();
+        let result = std::panic::catch_unwind(|| assert_matches_reference(&Canvas::new(4, 4), &path, Tolerance::default()));
+        let message = *result.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.ends_with("expected a 8x4 canvas, got 4x4"), "{}", message);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn render_differing_from_reference_panics_and_leaves_artifacts() {
+        let path = temp_path("mismatch.ppm");
+        write_reference(&gradient(), &path).unwrap();
+        let mut changed = gradient();
+        changed.write_pixel(3, 2, Color::new(1.0, 0.0, 0.0));
+        let result = std::panic::catch_unwind(|| assert_matches_reference(&changed, &path, Tolerance::default()));
+        assert!(result.is_err());
+        assert!(path.with_extension("actual.ppm").exists());
+        assert!(path.with_extension("diff.ppm").exists());
+        assert_matches_reference(&changed, &path, Tolerance::new(1.0 / 255.0, 1, 1.0));
+        for file in [path.clone(), path.with_extension("actual.ppm"), path.with_extension("diff.ppm")] {
+            let _ = std::fs::remove_file(file);
+        }
+    }
+}
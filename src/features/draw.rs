@@ -0,0 +1,364 @@
+use super::{canvas::Canvas, color::Color};
+
+// Integer primitives address pixels directly. Floating point primitives use canvas
+// coordinates, where pixel (x, y) covers [x, x + 1) x [y, y + 1) and its centre sits
+// at (x + 0.5, y + 0.5). Every primitive blends over the existing pixels with the
+// given alpha and silently clips anything outside the canvas.
+impl Canvas {
+    // A NaN or infinite alpha counts as zero and leaves the pixel alone.
+    pub fn blend_pixel(&mut self, x: isize, y: isize, color: Color, alpha: f64) {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height || !(alpha.is_finite() && alpha > 0.0) {
+            return;
+        }
+        let (x, y) = (x as usize, y as usize);
+        let alpha = alpha.min(1.0);
+        let blended = self.pixel_at(x, y) * (1.0 - alpha) + color * alpha;
+        self.write_pixel(x, y, blended);
+        if self.alpha.is_some() {
+            let coverage = self.alpha_at(x, y);
+            self.write_alpha(x, y, alpha + coverage * (1.0 - alpha));
+        }
+    }
+
+    pub fn draw_line(&mut self, x0: isize, y0: isize, x1: isize, y1: isize, color: Color, alpha: f64) {
+        let Some((x0, y0, x1, y1)) = self.clip_line(x0, y0, x1, y1) else { return };
+        let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+        let (step_x, step_y) = (if x0 < x1 { 1 } else { -1 }, if y0 < y1 { 1 } else { -1 });
+        let (mut x, mut y, mut error) = (x0, y0, dx + dy);
+        loop {
+            self.blend_pixel(x, y, color, alpha);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    // The part of the line from (x0, y0) to (x1, y1) that lies on the canvas, found
+    // with Liang-Barsky clipping and rounded back to pixels, or None if the line
+    // misses the canvas. Lines that start and end on the canvas are left as they are.
+    fn clip_line(&self, x0: isize, y0: isize, x1: isize, y1: isize) -> Option<(isize, isize, isize, isize)> {
+        let inside = |x: isize, y: isize| x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height;
+        if inside(x0, y0) && inside(x1, y1) {
+            return Some((x0, y0, x1, y1));
+        }
+        if self.width == 0 || self.height == 0 {
+            return None;
+        }
+        let (right, bottom) = ((self.width - 1) as f64, (self.height - 1) as f64);
+        let (start_x, start_y) = (x0 as f64, y0 as f64);
+        let (dx, dy) = (x1 as f64 - start_x, y1 as f64 - start_y);
+        let (mut enter, mut leave) = (0.0f64, 1.0f64);
+        for (direction, distance) in [(-dx, start_x), (dx, right - start_x), (-dy, start_y), (dy, bottom - start_y)] {
+            if direction == 0.0 {
+                if distance < 0.0 {
+                    return None;
+                }
+            } else if direction < 0.0 {
+                enter = enter.max(distance / direction);
+            } else {
+                leave = leave.min(distance / direction);
+            }
+        }
+        if enter > leave {
+            return None;
+        }
+        let point = |t: f64| ((start_x + t * dx).round() as isize, (start_y + t * dy).round() as isize);
+        let ((x0, y0), (x1, y1)) = (point(enter), point(leave));
+        Some((x0, y0, x1, y1))
+    }
+
+    // Xiaolin Wu's anti-aliased line. Only the columns (or rows, for steep lines) on
+    // the canvas are walked, so the line may run arbitrarily far off it.
+    pub fn draw_line_aa(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, color: Color, alpha: f64) {
+        if ![x0, y0, x1, y1].iter().all(|coordinate| coordinate.is_finite()) {
+            return;
+        }
+        // Wu's algorithm puts pixel centres on integer coordinates.
+        let (mut x0, mut y0, mut x1, mut y1) = (x0 - 0.5, y0 - 0.5, x1 - 0.5, y1 - 0.5);
+        let steep = (y1 - y0).abs() > (x1 - x0).abs();
+        if steep {
+            std::mem::swap(&mut x0, &mut y0);
+            std::mem::swap(&mut x1, &mut y1);
+        }
+        if x0 > x1 {
+            std::mem::swap(&mut x0, &mut x1);
+            std::mem::swap(&mut y0, &mut y1);
+        }
+        let plot = |canvas: &mut Canvas, x: f64, y: f64, coverage: f64| {
+            let (x, y) = if steep { (y, x) } else { (x, y) };
+            canvas.blend_pixel(x as isize, y as isize, color, alpha * coverage);
+        };
+        let fract = |value: f64| value - value.floor();
+
+        let dx = x1 - x0;
+        let gradient = if dx == 0.0 { 1.0 } else { (y1 - y0) / dx };
+
+        let x_start = x0.round();
+        let y_start = y0 + gradient * (x_start - x0);
+        let gap = 1.0 - fract(x0 + 0.5);
+        plot(self, x_start, y_start.floor(), (1.0 - fract(y_start)) * gap);
+        plot(self, x_start, y_start.floor() + 1.0, fract(y_start) * gap);
+
+        let x_end = x1.round();
+        let y_end = y1 + gradient * (x_end - x1);
+        let gap = fract(x1 + 0.5);
+        plot(self, x_end, y_end.floor(), (1.0 - fract(y_end)) * gap);
+        plot(self, x_end, y_end.floor() + 1.0, fract(y_end) * gap);
+
+        let limit = if steep { self.height } else { self.width } as f64;
+        let mut x = (x_start + 1.0).max(0.0);
+        let mut intersection = y_start + gradient * (x - x_start);
+        while x < x_end.min(limit) {
+            plot(self, x, intersection.floor(), 1.0 - fract(intersection));
+            plot(self, x, intersection.floor() + 1.0, fract(intersection));
+            intersection += gradient;
+            x += 1.0;
+        }
+    }
+
+    // Midpoint circle outline.
+    pub fn draw_circle(&mut self, center_x: isize, center_y: isize, radius: isize, color: Color, alpha: f64) {
+        let mut points = Vec::new();
+        let (mut x, mut y, mut error) = (radius, 0, 1 - radius);
+        while x >= y {
+            for (dx, dy) in [(x, y), (y, x), (-y, x), (-x, y), (-x, -y), (-y, -x), (y, -x), (x, -y)] {
+                points.push((center_x + dx, center_y + dy));
+            }
+            y += 1;
+            if error < 0 {
+                error += 2 * y + 1;
+            } else {
+                x -= 1;
+                error += 2 * (y - x) + 1;
+            }
+        }
+        // The octants meet on the axes and diagonals; blend those pixels only once.
+        points.sort_unstable();
+        points.dedup();
+        for (x, y) in points {
+            self.blend_pixel(x, y, color, alpha);
+        }
+    }
+
+    pub fn fill_circle(&mut self, center_x: f64, center_y: f64, radius: f64, color: Color, alpha: f64) {
+        let top = (center_y - radius).floor().max(0.0) as isize;
+        let bottom = (center_y + radius).ceil().min(self.height as f64) as isize;
+        let left = (center_x - radius).floor().max(0.0) as isize;
+        let right = (center_x + radius).ceil().min(self.width as f64) as isize;
+        for y in top..bottom {
+            for x in left..right {
+                let (dx, dy) = (x as f64 + 0.5 - center_x, y as f64 + 0.5 - center_y);
+                if dx * dx + dy * dy <= radius * radius {
+                    self.blend_pixel(x, y, color, alpha);
+                }
+            }
+        }
+    }
+
+    pub fn fill_rect(&mut self, x: isize, y: isize, width: usize, height: usize, color: Color, alpha: f64) {
+        let clip = |start: isize, length: usize, size: usize| {
+            let end = start.saturating_add(isize::try_from(length).unwrap_or(isize::MAX));
+            (start.max(0), end.min(size as isize))
+        };
+        let ((left, right), (top, bottom)) = (clip(x, width, self.width), clip(y, height, self.height));
+        for row in top..bottom {
+            for column in left..right {
+                self.blend_pixel(column, row, color, alpha);
+            }
+        }
+    }
+
+    // Fills the size x size square centred on (x, y).
+    pub fn draw_point(&mut self, x: f64, y: f64, size: f64, color: Color, alpha: f64) {
+        let half = size / 2.0;
+        let (left, top) = ((x - half).round() as isize, (y - half).round() as isize);
+        let (right, bottom) = ((x + half).round() as isize, (y + half).round() as isize);
+        self.fill_rect(left, top, right.saturating_sub(left).max(0) as usize, bottom.saturating_sub(top).max(0) as usize, color, alpha);
+    }
+
+    // Scanline fill using the even-odd rule, sampling each pixel at its centre.
+    pub fn fill_polygon(&mut self, points: &[(f64, f64)], color: Color, alpha: f64) {
+        if points.len() < 3 {
+            return;
+        }
+        let top = points.iter().map(|point| point.1).fold(f64::INFINITY, f64::min).floor().max(0.0) as usize;
+        let bottom = points.iter().map(|point| point.1).fold(f64::NEG_INFINITY, f64::max).ceil().min(self.height as f64);
+        let mut crossings = Vec::new();
+        for y in top..bottom.max(0.0) as usize {
+            let scan_y = y as f64 + 0.5;
+            crossings.clear();
+            for (index, &(x0, y0)) in points.iter().enumerate() {
+                let (x1, y1) = points[(index + 1) % points.len()];
+                if (y0 <= scan_y) != (y1 <= scan_y) {
+                    crossings.push(x0 + (scan_y - y0) / (y1 - y0) * (x1 - x0));
+                }
+            }
+            crossings.sort_by(|a, b| a.total_cmp(b));
+            for span in crossings.chunks_exact(2) {
+                let start = (span[0] - 0.5).ceil().clamp(0.0, self.width as f64) as isize;
+                let end = (span[1] - 0.5).ceil().clamp(0.0, self.width as f64) as isize;
+                for x in start..end {
+                    self.blend_pixel(x, y as isize, color, alpha);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::color::Rgba;
+
+    fn white() -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+
+    fn lit(canvas: &Canvas) -> Vec<(usize, usize)> {
+        canvas.enumerate_pixels().filter(|(_, _, pixel)| pixel.red > 0.0).map(|(x, y, _)| (x, y)).collect()
+    }
+
+    #[test]
+    fn blending_pixels() {
+        let mut c = Canvas::new(2, 2);
+        c.write_pixel(0, 0, Color::new(1.0, 0.0, 0.0));
+        c.blend_pixel(0, 0, Color::new(0.0, 0.0, 1.0), 0.25);
+        assert_eq!(c.pixel_at(0, 0), Color::new(0.75, 0.0, 0.25));
+        c.blend_pixel(-1, 0, white(), 1.0);
+        c.blend_pixel(0, 2, white(), 1.0);
+        assert_eq!(lit(&c), vec![(0, 0)]);
+    }
+
+    #[test]
+    fn non_finite_alpha_paints_nothing() {
+        let mut c = Canvas::new(1, 1);
+        c.write_alpha(0, 0, 0.0);
+        for alpha in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            c.blend_pixel(0, 0, white(), alpha);
+        }
+        assert_eq!(c.rgba_at(0, 0), Rgba::transparent());
+    }
+
+    #[test]
+    fn blending_onto_transparent_pixels_adds_coverage() {
+        let mut c = Canvas::new(1, 1);
+        c.write_alpha(0, 0, 0.0);
+        c.blend_pixel(0, 0, white(), 0.5);
+        assert_eq!(c.rgba_at(0, 0), Rgba::new(Color::new(0.5, 0.5, 0.5), 0.5));
+    }
+
+    #[test]
+    fn bresenham_lines() {
+        let mut c = Canvas::new(5, 5);
+        c.draw_line(0, 0, 4, 2, white(), 1.0);
+        assert_eq!(lit(&c), vec![(0, 0), (1, 1), (2, 1), (3, 2), (4, 2)]);
+
+        let mut c = Canvas::new(3, 3);
+        c.draw_line(1, 2, 1, 0, white(), 1.0);
+        assert_eq!(lit(&c), vec![(1, 0), (1, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn lines_far_off_the_canvas_are_clipped() {
+        let mut c = Canvas::new(5, 3);
+        c.draw_line(0, 1, isize::MAX, 1, white(), 1.0);
+        assert_eq!(lit(&c), vec![(0, 1), (1, 1), (2, 1), (3, 1), (4, 1)]);
+        c.draw_line(isize::MIN, isize::MIN, isize::MAX, isize::MIN, white(), 1.0);
+        c.draw_line(-10, 0, -1, 2, white(), 1.0);
+        assert_eq!(lit(&c).len(), 5);
+    }
+
+    #[test]
+    fn anti_aliased_line_along_a_row() {
+        let mut c = Canvas::new(6, 3);
+        c.draw_line_aa(0.5, 1.5, 5.5, 1.5, white(), 1.0);
+        assert_eq!(c.pixel_at(0, 1), Color::new(0.5, 0.5, 0.5));
+        assert_eq!(c.pixel_at(3, 1), white());
+        assert_eq!(c.pixel_at(5, 1), Color::new(0.5, 0.5, 0.5));
+        assert_eq!(c.pixel_at(3, 0), Color::new(0.0, 0.0, 0.0));
+        assert_eq!(c.pixel_at(3, 2), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn anti_aliased_line_between_rows_splits_coverage() {
+        let mut c = Canvas::new(6, 3);
+        c.draw_line_aa(0.5, 1.0, 5.5, 1.0, white(), 1.0);
+        assert_eq!(c.pixel_at(2, 0), Color::new(0.5, 0.5, 0.5));
+        assert_eq!(c.pixel_at(2, 1), Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn anti_aliased_lines_far_off_the_canvas_finish() {
+        let mut c = Canvas::new(10, 10);
+        c.draw_line_aa(0.5, 0.5, 1e10, 1.0, white(), 1.0);
+        assert_eq!(c.pixel_at(5, 0), white());
+        c.draw_line_aa(-1e12, 5.5, 5.5, 1e13, white(), 1.0);
+        c.draw_line_aa(0.0, 0.0, f64::INFINITY, 1.0, white(), 1.0);
+        c.draw_line_aa(f64::NAN, 0.0, 5.0, 5.0, white(), 1.0);
+    }
+
+    #[test]
+    fn circle_outline_is_symmetric() {
+        let mut c = Canvas::new(7, 7);
+        c.draw_circle(3, 3, 2, white(), 0.5);
+        let points = lit(&c);
+        assert_eq!(points.len(), 12);
+        for (x, y) in points {
+            assert!(lit(&c).contains(&(6 - x, y)));
+            assert_eq!(c.pixel_at(x, y), Color::new(0.5, 0.5, 0.5));
+        }
+    }
+
+    #[test]
+    fn filling_circles() {
+        let mut c = Canvas::new(4, 4);
+        c.fill_circle(2.0, 2.0, 1.0, white(), 1.0);
+        assert_eq!(lit(&c), vec![(1, 1), (2, 1), (1, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn filling_rectangles_clips_to_canvas() {
+        let mut c = Canvas::new(3, 3);
+        c.fill_rect(-1, 1, 3, 5, white(), 1.0);
+        assert_eq!(lit(&c), vec![(0, 1), (1, 1), (0, 2), (1, 2)]);
+        c.fill_rect(isize::MAX - 2, 0, 10, 1, white(), 1.0);
+        c.fill_rect(-5, -5, usize::MAX, usize::MAX, white(), 1.0);
+        assert_eq!(lit(&c).len(), 9);
+
+        let mut c = Canvas::new(3, 3);
+        c.fill_circle(1.5, 1.5, 1e300, white(), 1.0);
+        assert_eq!(lit(&c).len(), 9);
+    }
+
+    #[test]
+    fn thick_points_cover_a_square() {
+        let mut c = Canvas::new(4, 4);
+        c.draw_point(2.0, 2.0, 2.0, white(), 1.0);
+        assert_eq!(lit(&c), vec![(1, 1), (2, 1), (1, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn filling_polygons() {
+        let mut c = Canvas::new(4, 4);
+        c.fill_polygon(&[(0.0, 0.0), (4.0, 0.0), (0.0, 4.0)], white(), 1.0);
+        assert_eq!(lit(&c), vec![(0, 0), (1, 0), (2, 0), (0, 1), (1, 1), (0, 2)]);
+    }
+
+    #[test]
+    fn filling_polygon_with_hole_uses_even_odd_rule() {
+        let mut c = Canvas::new(5, 5);
+        let square_with_hole = [(0.0, 0.0), (5.0, 0.0), (5.0, 5.0), (0.0, 5.0), (0.0, 0.0), (2.0, 2.0), (2.0, 3.0), (3.0, 3.0), (3.0, 2.0), (2.0, 2.0)];
+        c.fill_polygon(&square_with_hole, white(), 1.0);
+        assert_eq!(lit(&c).len(), 24);
+        assert_eq!(c.pixel_at(2, 2), Color::new(0.0, 0.0, 0.0));
+    }
+}
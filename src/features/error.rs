@@ -0,0 +1,65 @@
+use std::{fmt, io};
+
+use super::{canvas::ImageError, scene::SceneError};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Image(ImageError),
+    Scene(SceneError),
+    InvalidScene { errors: usize },
+    NotSquare { rows: usize, columns: usize },
+    NotInvertible,
+    DimensionMismatch { left: (usize, usize), right: (usize, usize) },
+    CanvasSizeMismatch { expected: (usize, usize), actual: (usize, usize) },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "I/O error: {}", error),
+            Error::Image(error) => write!(f, "{}", error),
+            Error::Scene(error) => write!(f, "scene error at {}", error),
+            Error::InvalidScene { errors } => write!(f, "the scene failed validation with {} errors", errors),
+            Error::NotSquare { rows, columns } => write!(f, "matrix must be square, got {}x{}", rows, columns),
+            Error::NotInvertible => write!(f, "matrix is not invertible"),
+            Error::DimensionMismatch { left, right } => {
+                write!(f, "matrix dimensions {}x{} and {}x{} do not match", left.0, left.1, right.0, right.1)
+            }
+            Error::CanvasSizeMismatch { expected, actual } => {
+                write!(f, "expected a {}x{} canvas, got {}x{}", expected.0, expected.1, actual.0, actual.1)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            Error::Image(error) => Some(error),
+            Error::Scene(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<ImageError> for Error {
+    fn from(error: ImageError) -> Self {
+        Error::Image(error)
+    }
+}
+
+impl From<SceneError> for Error {
+    fn from(error: SceneError) -> Self {
+        Error::Scene(error)
+    }
+}
@@ -0,0 +1,344 @@
+use std::f64::consts::PI;
+
+use super::{canvas::Canvas, color::Color};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResizeFilter {
+    Bilinear,
+    Lanczos3,
+}
+
+impl ResizeFilter {
+    fn support(&self) -> f64 {
+        match self {
+            ResizeFilter::Bilinear => 1.0,
+            ResizeFilter::Lanczos3 => 3.0,
+        }
+    }
+
+    fn weight(&self, x: f64) -> f64 {
+        let x = x.abs();
+        match self {
+            ResizeFilter::Bilinear => (1.0 - x).max(0.0),
+            ResizeFilter::Lanczos3 => {
+                if x < 1e-12 {
+                    1.0
+                } else if x < 3.0 {
+                    let pi_x = PI * x;
+                    3.0 * pi_x.sin() * (pi_x / 3.0).sin() / (pi_x * pi_x)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+// Three standard deviations either side, but no wider than `max_radius`.
+fn gaussian_kernel(sigma: f64, max_radius: usize) -> Vec<f64> {
+    let radius = ((3.0 * sigma).ceil().max(0.0) as usize).min(max_radius);
+    let mut kernel: Vec<f64> = (0..=2 * radius)
+        .map(|i| {
+            let x = i as f64 - radius as f64;
+            (-(x * x) / (2.0 * sigma * sigma)).exp()
+        })
+        .collect();
+    let sum: f64 = kernel.iter().sum();
+    kernel.iter_mut().for_each(|weight| *weight /= sum);
+    kernel
+}
+
+fn weighted_sum(weights: &[(usize, f64)], sample: impl Fn(usize) -> Color) -> Color {
+    weights.iter().fold(Color::new(0.0, 0.0, 0.0), |sum, &(index, weight)| sum + sample(index) * weight)
+}
+
+// For each output position along an axis of `source_length` pixels, the source pixels
+// it draws from and their normalized weights. Downscaling widens the filter so every
+// source pixel still contributes.
+fn resample_weights(source_length: usize, target_length: usize, filter: ResizeFilter) -> Vec<Vec<(usize, f64)>> {
+    let scale = target_length as f64 / source_length as f64;
+    let stretch = (1.0 / scale).max(1.0);
+    let support = filter.support() * stretch;
+    (0..target_length)
+        .map(|i| {
+            let center = (i as f64 + 0.5) / scale - 0.5;
+            let first = (center - support).ceil() as isize;
+            let last = (center + support).floor() as isize;
+            let mut weights: Vec<(usize, f64)> = (first..=last)
+                .map(|j| {
+                    let index = j.clamp(0, source_length as isize - 1) as usize;
+                    (index, filter.weight((j as f64 - center) / stretch))
+                })
+                .filter(|(_, weight)| *weight != 0.0)
+                .collect();
+            let total: f64 = weights.iter().map(|(_, weight)| weight).sum();
+            if total == 0.0 {
+                return vec![(center.round().clamp(0.0, source_length as f64 - 1.0) as usize, 1.0)];
+            }
+            weights.iter_mut().for_each(|(_, weight)| *weight /= total);
+            weights
+        })
+        .collect()
+}
+
+// The alpha plane as a grey canvas, so it can go through the same filters as colour.
+fn alpha_as_canvas(canvas: &Canvas) -> Option<Canvas> {
+    let alpha = canvas.alpha.as_ref()?;
+    let mut grey = Canvas::new(canvas.width, canvas.height);
+    grey.pixels = alpha.iter().map(|&a| Color::new(a, a, a)).collect();
+    Some(grey)
+}
+
+fn alpha_from_canvas(grey: Canvas) -> Vec<f64> {
+    grey.pixels.iter().map(|pixel| pixel.red).collect()
+}
+
+// All filters return a new canvas and leave the original untouched. Blurs clamp at
+// the edges, so border pixels are treated as extending outwards.
+impl Canvas {
+    // Convolves rows and then columns with the same normalized 1D kernel.
+    pub fn convolve_separable(&self, kernel: &[f64]) -> Canvas {
+        let radius = (kernel.len() / 2) as isize;
+        let weights = |position: usize, length: usize| -> Vec<(usize, f64)> {
+            kernel
+                .iter()
+                .enumerate()
+                .map(|(k, &weight)| ((position as isize + k as isize - radius).clamp(0, length as isize - 1) as usize, weight))
+                .collect()
+        };
+
+        let mut horizontal = Canvas::new(self.width, self.height);
+        for x in 0..self.width {
+            let taps = weights(x, self.width);
+            for y in 0..self.height {
+                let row = self.row(y);
+                horizontal.write_pixel(x, y, weighted_sum(&taps, |i| row[i]));
+            }
+        }
+        let mut result = Canvas::new(self.width, self.height);
+        result.export = self.export;
+        for y in 0..self.height {
+            let taps = weights(y, self.height);
+            for x in 0..self.width {
+                result.write_pixel(x, y, weighted_sum(&taps, |i| horizontal.pixel_at(x, i)));
+            }
+        }
+        result.alpha = alpha_as_canvas(self).map(|grey| alpha_from_canvas(grey.convolve_separable(kernel)));
+        result
+    }
+
+    pub fn box_blur(&self, radius: usize) -> Canvas {
+        let size = 2 * radius + 1;
+        self.convolve_separable(&vec![1.0 / size as f64; size])
+    }
+
+    // Edges are clamped, so a kernel wider than the canvas would only repeat them. Below
+    // a thousandth of a pixel the kernel has no real width, and its weights would
+    // underflow to NaN, so the canvas is returned as it is.
+    pub fn gaussian_blur(&self, sigma: f64) -> Canvas {
+        if sigma.is_nan() || sigma < 1e-3 {
+            return self.crop(0, 0, self.width, self.height);
+        }
+        self.convolve_separable(&gaussian_kernel(sigma, self.width.max(self.height)))
+    }
+
+    pub fn resize(&self, width: usize, height: usize, filter: ResizeFilter) -> Canvas {
+        let mut result = Canvas::new(width, height);
+        result.export = self.export;
+        if self.width == 0 || self.height == 0 {
+            return result;
+        }
+        let columns = resample_weights(self.width, width, filter);
+        let mut horizontal = Canvas::new(width, self.height);
+        for y in 0..self.height {
+            let row = self.row(y);
+            for (x, taps) in columns.iter().enumerate() {
+                horizontal.write_pixel(x, y, weighted_sum(taps, |i| row[i]));
+            }
+        }
+        let rows = resample_weights(self.height, height, filter);
+        for (y, taps) in rows.iter().enumerate() {
+            for x in 0..width {
+                result.write_pixel(x, y, weighted_sum(taps, |i| horizontal.pixel_at(x, i)));
+            }
+        }
+        result.alpha = alpha_as_canvas(self).map(|grey| alpha_from_canvas(grey.resize(width, height, filter)));
+        result
+    }
+
+    pub fn crop(&self, x: usize, y: usize, width: usize, height: usize) -> Canvas {
+        self.region(x, y, width, height).to_canvas()
+    }
+
+    pub fn flip_horizontal(&self) -> Canvas {
+        let mut result = self.crop(0, 0, self.width, self.height);
+        result.rows_mut().for_each(|row| row.reverse());
+        if let Some(alpha) = result.alpha.as_mut() {
+            alpha.chunks_mut(self.width.max(1)).for_each(|row| row.reverse());
+        }
+        result
+    }
+
+    pub fn flip_vertical(&self) -> Canvas {
+        let mut result = Canvas::new(self.width, self.height);
+        result.export = self.export;
+        for (target, source) in result.rows_mut().zip(self.rows().rev()) {
+            target.copy_from_slice(source);
+        }
+        result.alpha = self.alpha.as_ref().map(|alpha| alpha.chunks(self.width.max(1)).rev().flatten().copied().collect());
+        result
+    }
+
+    // Adds a blurred copy of everything brighter than `threshold` back onto the image,
+    // so highlights above 1.0 glow into their surroundings.
+    pub fn bloom(&self, threshold: f64, sigma: f64, strength: f64) -> Canvas {
+        let mut bright = self.crop(0, 0, self.width, self.height);
+        for pixel in bright.pixels.iter_mut() {
+            *pixel = Color::new(
+                (pixel.red - threshold).max(0.0),
+                (pixel.green - threshold).max(0.0),
+                (pixel.blue - threshold).max(0.0),
+            );
+        }
+        let glow = bright.gaussian_blur(sigma);
+        let mut result = self.crop(0, 0, self.width, self.height);
+        for (pixel, glow) in result.pixels.iter_mut().zip(glow.pixels.iter()) {
+            *pixel = *pixel + *glow * strength;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::util::almost_equal;
+
+    fn filled(width: usize, height: usize, color: Color) -> Canvas {
+        let mut c = Canvas::new(width, height);
+        c.pixels.iter_mut().for_each(|pixel| *pixel = color);
+        c
+    }
+
+    fn total(canvas: &Canvas) -> f64 {
+        canvas.pixels.iter().map(|pixel| pixel.red).sum()
+    }
+
+    #[test]
+    fn gaussian_kernel_is_normalized_and_symmetric() {
+        let kernel = gaussian_kernel(1.5, 100);
+        assert_eq!(kernel.len(), 11);
+        assert!(almost_equal(kernel.iter().sum(), 1.0));
+        assert!(almost_equal(kernel[0], kernel[10]));
+        assert!(kernel[5] > kernel[4]);
+        assert_eq!(gaussian_kernel(1.5, 2).len(), 5);
+    }
+
+    #[test]
+    fn degenerate_sigmas_are_safe() {
+        let c = filled(6, 4, Color::new(0.2, 0.4, 0.6));
+        assert_eq!(c.gaussian_blur(f64::NAN).pixels, c.pixels);
+        assert_eq!(c.gaussian_blur(-1.0).pixels, c.pixels);
+        assert_eq!(c.gaussian_blur(1e-200).pixels, c.pixels);
+        assert_eq!(c.gaussian_blur(1e-4).pixels, c.pixels);
+        let blurred = c.gaussian_blur(1e300);
+        assert!(blurred.pixels.iter().zip(c.pixels.iter()).all(|(a, b)| almost_equal(a.red, b.red) && almost_equal(a.blue, b.blue)));
+    }
+
+    #[test]
+    fn blurring_a_flat_canvas_changes_nothing() {
+        let c = filled(6, 4, Color::new(0.2, 0.4, 0.6));
+        assert_eq!(c.box_blur(2).pixels, c.pixels);
+        assert_eq!(c.gaussian_blur(1.0).pixels, c.pixels);
+    }
+
+    #[test]
+    fn box_blur_spreads_a_point_evenly() {
+        let mut c = Canvas::new(7, 7);
+        c.write_pixel(3, 3, Color::new(9.0, 0.0, 0.0));
+        let blurred = c.box_blur(1);
+        assert_eq!(blurred.pixel_at(2, 2), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(blurred.pixel_at(3, 3), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(blurred.pixel_at(1, 3), Color::new(0.0, 0.0, 0.0));
+        assert!(almost_equal(total(&blurred), 9.0));
+    }
+
+    #[test]
+    fn gaussian_blur_conserves_energy_away_from_edges() {
+        let mut c = Canvas::new(21, 21);
+        c.write_pixel(10, 10, Color::new(1.0, 0.0, 0.0));
+        let blurred = c.gaussian_blur(1.0);
+        assert!(almost_equal(total(&blurred), 1.0));
+        assert!(blurred.pixel_at(10, 10).red > blurred.pixel_at(11, 10).red);
+    }
+
+    #[test]
+    fn resizing_keeps_flat_colors() {
+        let c = filled(8, 6, Color::new(0.5, 0.25, 1.0));
+        for filter in [ResizeFilter::Bilinear, ResizeFilter::Lanczos3] {
+            for (width, height) in [(16, 12), (3, 2), (8, 6)] {
+                let resized = c.resize(width, height, filter);
+                assert_eq!((resized.width, resized.height), (width, height));
+                assert!(resized.pixels.iter().all(|pixel| *pixel == Color::new(0.5, 0.25, 1.0)));
+            }
+        }
+    }
+
+    #[test]
+    fn bilinear_upscale_interpolates() {
+        let mut c = Canvas::new(2, 1);
+        c.write_pixel(1, 0, Color::new(1.0, 1.0, 1.0));
+        let resized = c.resize(4, 1, ResizeFilter::Bilinear);
+        assert_eq!(resized.pixel_at(0, 0), Color::new(0.0, 0.0, 0.0));
+        assert_eq!(resized.pixel_at(1, 0), Color::new(0.25, 0.25, 0.25));
+        assert_eq!(resized.pixel_at(2, 0), Color::new(0.75, 0.75, 0.75));
+        assert_eq!(resized.pixel_at(3, 0), Color::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn downscaling_averages_source_pixels() {
+        let mut c = Canvas::new(4, 1);
+        c.write_pixel(0, 0, Color::new(1.0, 1.0, 1.0));
+        c.write_pixel(1, 0, Color::new(1.0, 1.0, 1.0));
+        let resized = c.resize(2, 1, ResizeFilter::Bilinear);
+        assert!(resized.pixel_at(0, 0).red > 0.7);
+        assert!(resized.pixel_at(1, 0).red < 0.3);
+    }
+
+    #[test]
+    fn cropping_and_flipping() {
+        let mut c = Canvas::new(3, 2);
+        c.write_pixel(0, 0, Color::new(1.0, 0.0, 0.0));
+        let cropped = c.crop(0, 0, 2, 1);
+        assert_eq!((cropped.width, cropped.height), (2, 1));
+        assert_eq!(cropped.pixel_at(0, 0), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(c.flip_horizontal().pixel_at(2, 0), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(c.flip_vertical().pixel_at(0, 1), Color::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn bloom_spreads_only_over_bright_values() {
+        let mut c = filled(9, 9, Color::new(0.5, 0.5, 0.5));
+        let dim = c.bloom(1.0, 1.0, 1.0);
+        assert_eq!(dim.pixels, c.pixels);
+
+        c.write_pixel(4, 4, Color::new(5.0, 0.5, 0.5));
+        let bloomed = c.bloom(1.0, 1.0, 1.0);
+        assert!(bloomed.pixel_at(5, 4).red > 0.5);
+        assert_eq!(bloomed.pixel_at(5, 4).green, 0.5);
+        assert_eq!(bloomed.pixel_at(0, 0), Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn filters_carry_the_alpha_plane() {
+        let mut c = Canvas::new(4, 2);
+        c.write_alpha(0, 0, 0.0);
+        assert_eq!(c.flip_horizontal().alpha_at(3, 0), 0.0);
+        assert_eq!(c.flip_vertical().alpha_at(0, 1), 0.0);
+        assert_eq!(c.crop(0, 0, 2, 2).alpha_at(0, 0), 0.0);
+        assert_eq!(c.crop(1, 0, 2, 2).alpha_at(0, 0), 1.0);
+        assert!(almost_equal(c.box_blur(1).alpha_at(1, 0), 7.0 / 9.0));
+        assert_eq!(c.resize(8, 4, ResizeFilter::Bilinear).alpha_at(7, 3), 1.0);
+        assert!(Canvas::new(2, 2).box_blur(1).alpha.is_none());
+    }
+}
@@ -0,0 +1,405 @@
+use std::io::{self, Write};
+
+use super::{
+    canvas::{Canvas, HeaderParser, ImageError},
+    color::Color,
+};
+
+pub fn color_to_rgbe(color: Color) -> [u8; 4] {
+    // NaN and negative channels encode as zero. Infinite ones saturate to the largest
+    // finite value, and so to exponent 127.
+    let channel = |value: f64| if value == f64::INFINITY { f64::MAX } else { value.max(0.0) };
+    let (red, green, blue) = (channel(color.red), channel(color.green), channel(color.blue));
+    let largest = red.max(green).max(blue);
+    if largest < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    // Shared exponent e with largest = m * 2^e and m in [0.5, 1).
+    let mut exponent = largest.log2().floor() as i32 + 1;
+    if largest / 2f64.powi(exponent) >= 1.0 {
+        exponent += 1;
+    }
+    let exponent = exponent.clamp(-128, 127);
+    let scale = 256.0 / 2f64.powi(exponent);
+    let mantissa = |channel: f64| (channel * scale).min(255.0) as u8;
+    [mantissa(red), mantissa(green), mantissa(blue), (exponent + 128) as u8]
+}
+
+pub fn rgbe_to_color(rgbe: [u8; 4]) -> Color {
+    if rgbe[3] == 0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    let scale = 2f64.powi(rgbe[3] as i32 - (128 + 8));
+    Color::new((rgbe[0] as f64 + 0.5) * scale, (rgbe[1] as f64 + 0.5) * scale, (rgbe[2] as f64 + 0.5) * scale)
+}
+
+// Run-length encodes one channel of a scanline the way Radiance does: runs of at
+// least four equal bytes become (128 + count, byte), everything else is copied
+// through in literal dumps of up to 128 bytes.
+fn encode_channel(data: &[u8], out: &mut Vec<u8>) {
+    const MIN_RUN: usize = 4;
+    let mut current = 0;
+    while current < data.len() {
+        let mut run_start = current;
+        let mut run_length = 0;
+        while run_length < MIN_RUN && run_start < data.len() {
+            run_start += run_length;
+            run_length = 1;
+            while run_start + run_length < data.len() && run_length < 127 && data[run_start + run_length] == data[run_start] {
+                run_length += 1;
+            }
+        }
+        let dump_end = run_start.min(data.len());
+        while current < dump_end {
+            let count = (dump_end - current).min(128);
+            out.push(count as u8);
+            out.extend_from_slice(&data[current..current + count]);
+            current += count;
+        }
+        if run_length >= MIN_RUN {
+            out.push(128 + run_length as u8);
+            out.push(data[run_start]);
+            current += run_length;
+        }
+    }
+}
+
+fn take<'a>(data: &'a [u8], position: &mut usize, count: usize, y: usize) -> Result<&'a [u8], ImageError> {
+    let bytes = data
+        .get(*position..*position + count)
+        .ok_or_else(|| ImageError::Truncated(format!("Radiance HDR scanline {} is incomplete", y)))?;
+    *position += count;
+    Ok(bytes)
+}
+
+fn read_scanline(data: &[u8], position: &mut usize, width: usize, y: usize) -> Result<Vec<[u8; 4]>, ImageError> {
+    let mut pixels = vec![[0u8; 4]; width];
+    let peek = data.get(*position..*position + 4);
+    let is_rle = (8..=0x7fff).contains(&width) && matches!(peek, Some([2, 2, high, low]) if (*high as usize) << 8 | *low as usize == width);
+
+    if is_rle {
+        *position += 4;
+        for channel in 0..4 {
+            let mut x = 0;
+            while x < width {
+                let count = take(data, position, 1, y)?[0] as usize;
+                if count > 128 {
+                    let count = count - 128;
+                    if x + count > width {
+                        return Err(ImageError::Malformed(format!("Radiance HDR run overflows scanline {}", y)));
+                    }
+                    let value = take(data, position, 1, y)?[0];
+                    for pixel in pixels[x..x + count].iter_mut() {
+                        pixel[channel] = value;
+                    }
+                    x += count;
+                } else {
+                    if count == 0 || x + count > width {
+                        return Err(ImageError::Malformed(format!("Radiance HDR dump overflows scanline {}", y)));
+                    }
+                    let values = take(data, position, count, y)?;
+                    for (pixel, &value) in pixels[x..x + count].iter_mut().zip(values) {
+                        pixel[channel] = value;
+                    }
+                    x += count;
+                }
+            }
+        }
+        return Ok(pixels);
+    }
+
+    // Flat scanlines, possibly using the old (1, 1, 1, count) repeat encoding.
+    let mut x = 0;
+    let mut shift = 0;
+    while x < width {
+        let bytes = take(data, position, 4, y)?;
+        if bytes[..3] == [1, 1, 1] {
+            if x == 0 {
+                return Err(ImageError::Malformed(format!("Radiance HDR repeat at start of scanline {}", y)));
+            }
+            if bytes[3] == 0 {
+                return Err(ImageError::Malformed(format!("Radiance HDR empty repeat in scanline {}", y)));
+            }
+            let count = (bytes[3] as usize).checked_shl(shift).filter(|&count| count.checked_add(x).is_some_and(|end| end <= width));
+            let Some(count) = count else {
+                return Err(ImageError::Malformed(format!("Radiance HDR repeat overflows scanline {}", y)));
+            };
+            let previous = pixels[x - 1];
+            pixels[x..x + count].fill(previous);
+            x += count;
+            shift += 8;
+        } else {
+            pixels[x] = [bytes[0], bytes[1], bytes[2], bytes[3]];
+            x += 1;
+            shift = 0;
+        }
+    }
+    Ok(pixels)
+}
+
+impl Canvas {
+    // Writes a Portable Float Map: little-endian f32 samples, bottom row first.
+    pub fn write_pfm<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write!(writer, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+        let mut bytes = Vec::with_capacity(self.width * 12);
+        for row in self.rows().rev() {
+            for pixel in row.iter() {
+                for channel in [pixel.red, pixel.green, pixel.blue] {
+                    bytes.extend_from_slice(&(channel as f32).to_le_bytes());
+                }
+            }
+            writer.write_all(&bytes)?;
+            bytes.clear();
+        }
+        writer.flush()
+    }
+
+    // Reads colour (PF) and greyscale (Pf) float maps of either byte order. The
+    // magnitude of the scale field is ignored; samples are taken as stored.
+    pub fn from_pfm(data: &[u8]) -> Result<Self, ImageError> {
+        let mut parser = HeaderParser { data, position: 0 };
+        let channels = match parser.token("magic number")? {
+            b"PF" => 3,
+            b"Pf" => 1,
+            magic => return Err(ImageError::UnsupportedFormat(format!("PFM magic number '{}'", String::from_utf8_lossy(magic)))),
+        };
+        let width = parser.number("width")?;
+        let height = parser.number("height")?;
+        let scale = parser.float("scale")?;
+        if scale == 0.0 || !scale.is_finite() {
+            return Err(ImageError::Malformed(format!("PFM scale {} must be non-zero", scale)));
+        }
+        parser.raster_start()?;
+
+        let expected = width.checked_mul(height).and_then(|pixels| pixels.checked_mul(channels * 4));
+        let raster = &data[parser.position..];
+        match expected {
+            Some(expected) if expected <= raster.len() => {}
+            _ => return Err(ImageError::Truncated(format!("{}x{} PFM raster does not fit in {} bytes", width, height, raster.len()))),
+        }
+
+        let mut canvas = Canvas::new(width, height);
+        let mut samples = raster.chunks_exact(4).map(|bytes| {
+            let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+            if scale < 0.0 {
+                f32::from_le_bytes(bytes) as f64
+            } else {
+                f32::from_be_bytes(bytes) as f64
+            }
+        });
+        for y in (0..height).rev() {
+            for x in 0..width {
+                let color = if channels == 3 {
+                    let red = samples.next().unwrap();
+                    let green = samples.next().unwrap();
+                    let blue = samples.next().unwrap();
+                    Color::new(red, green, blue)
+                } else {
+                    let value = samples.next().unwrap();
+                    Color::new(value, value, value)
+                };
+                canvas.write_pixel(x, y, color);
+            }
+        }
+        Ok(canvas)
+    }
+
+    // Writes a Radiance RGBE image, run-length encoding scanlines where the format
+    // allows it. Negative channels are clamped to zero.
+    pub fn write_hdr<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write!(writer, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", self.height, self.width)?;
+        let rle = (8..=0x7fff).contains(&self.width);
+        let mut bytes = Vec::new();
+        let mut channel = vec![0u8; self.width];
+        for row in self.rows() {
+            let rgbe: Vec<[u8; 4]> = row.iter().map(|pixel| color_to_rgbe(*pixel)).collect();
+            if rle {
+                bytes.extend_from_slice(&[2, 2, (self.width >> 8) as u8, self.width as u8]);
+                for index in 0..4 {
+                    for (value, pixel) in channel.iter_mut().zip(rgbe.iter()) {
+                        *value = pixel[index];
+                    }
+                    encode_channel(&channel, &mut bytes);
+                }
+            } else {
+                bytes.extend(rgbe.iter().flatten());
+            }
+            writer.write_all(&bytes)?;
+            bytes.clear();
+        }
+        writer.flush()
+    }
+
+    // Reads Radiance RGBE images in the standard -Y/+X orientation, with flat, old-style
+    // or new-style run-length encoded scanlines.
+    pub fn from_hdr(data: &[u8]) -> Result<Self, ImageError> {
+        let mut lines = data.split(|&byte| byte == b'\n');
+        let mut position = 0;
+        let mut next_line = |what: &str| {
+            let line = lines.next().filter(|_| position < data.len());
+            let line = line.ok_or_else(|| ImageError::Truncated(format!("Radiance HDR header ends before {}", what)))?;
+            position += line.len() + 1;
+            Ok::<_, ImageError>(String::from_utf8_lossy(line).trim_end_matches('\r').to_string())
+        };
+
+        let magic = next_line("the magic number")?;
+        if !magic.starts_with("#?") {
+            return Err(ImageError::UnsupportedFormat(format!("Radiance HDR magic number '{}'", magic)));
+        }
+        loop {
+            let line = next_line("the resolution line")?;
+            if line.is_empty() {
+                break;
+            }
+            if let Some(format) = line.strip_prefix("FORMAT=") {
+                if format != "32-bit_rle_rgbe" {
+                    return Err(ImageError::UnsupportedFormat(format!("Radiance HDR pixel format '{}'", format)));
+                }
+            }
+        }
+        let resolution = next_line("the resolution line")?;
+        let fields: Vec<&str> = resolution.split_whitespace().collect();
+        let (height, width) = match fields[..] {
+            ["-Y", height, "+X", width] => (height.parse::<usize>().ok(), width.parse::<usize>().ok()),
+            _ => return Err(ImageError::UnsupportedFormat(format!("Radiance HDR orientation '{}'", resolution))),
+        };
+        let (Some(height), Some(width)) = (height, width) else {
+            return Err(ImageError::Malformed(format!("Radiance HDR resolution '{}'", resolution)));
+        };
+        if width.checked_mul(height).is_none_or(|pixels| pixels > data.len().saturating_sub(position).saturating_mul(128)) {
+            return Err(ImageError::Truncated(format!("{}x{} Radiance HDR image does not fit in {} bytes", width, height, data.len())));
+        }
+
+        let mut canvas = Canvas::new(width, height);
+        for y in 0..height {
+            for (x, rgbe) in read_scanline(data, &mut position, width, y)?.into_iter().enumerate() {
+                canvas.write_pixel(x, y, rgbe_to_color(rgbe));
+            }
+        }
+        Ok(canvas)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Color, b: Color, tolerance: f64) -> bool {
+        let limit = tolerance * a.red.max(a.green).max(a.blue).max(1e-9);
+        (a.red - b.red).abs() <= limit && (a.green - b.green).abs() <= limit && (a.blue - b.blue).abs() <= limit
+    }
+
+    fn bright_canvas(width: usize, height: usize) -> Canvas {
+        let mut c = Canvas::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let value = if x < width / 2 { 12.5 } else { x as f64 * 0.37 + y as f64 };
+                c.write_pixel(x, y, Color::new(value, value * 0.5, 0.001 * x as f64));
+            }
+        }
+        c
+    }
+
+    #[test]
+    fn rgbe_round_trip_keeps_values_above_one() {
+        for color in [Color::new(1.0, 0.5, 0.25), Color::new(100.0, 3.0, 0.0), Color::new(0.001, 0.002, 0.003)] {
+            assert!(close(color, rgbe_to_color(color_to_rgbe(color)), 0.01));
+        }
+        assert_eq!(color_to_rgbe(Color::new(0.0, 0.0, 0.0)), [0, 0, 0, 0]);
+        assert_eq!(color_to_rgbe(Color::new(1.0, 0.0, 0.0)), [128, 0, 0, 129]);
+    }
+
+    #[test]
+    fn rgbe_saturates_infinite_channels() {
+        assert_eq!(color_to_rgbe(Color::new(f64::INFINITY, 0.0, 0.0)), [255, 0, 0, 255]);
+        assert_eq!(color_to_rgbe(Color::new(f64::NAN, f64::NEG_INFINITY, 0.0)), [0, 0, 0, 0]);
+        let mut c = Canvas::new(2, 1);
+        c.write_pixel(0, 0, Color::new(f64::INFINITY, 1.0, 0.0));
+        let mut hdr = Vec::new();
+        c.write_hdr(&mut hdr).unwrap();
+        assert_eq!(Canvas::from_hdr(&hdr).unwrap().width, 2);
+    }
+
+    #[test]
+    fn pfm_round_trip_preserves_float_values() {
+        let c = bright_canvas(5, 3);
+        let mut pfm = Vec::new();
+        c.write_pfm(&mut pfm).unwrap();
+        assert!(pfm.starts_with(b"PF\n5 3\n-1.0\n"));
+        let read = Canvas::from_pfm(&pfm).unwrap();
+        for y in 0..3 {
+            for x in 0..5 {
+                assert!(close(c.pixel_at(x, y), read.pixel_at(x, y), 1e-6));
+            }
+        }
+    }
+
+    #[test]
+    fn reading_big_endian_greyscale_pfm() {
+        let mut pfm = b"Pf\n2 1\n1.0\n".to_vec();
+        pfm.extend_from_slice(&2.5f32.to_be_bytes());
+        pfm.extend_from_slice(&0.25f32.to_be_bytes());
+        let c = Canvas::from_pfm(&pfm).unwrap();
+        assert_eq!(c.pixel_at(0, 0), Color::new(2.5, 2.5, 2.5));
+        assert_eq!(c.pixel_at(1, 0), Color::new(0.25, 0.25, 0.25));
+    }
+
+    #[test]
+    fn hdr_round_trip_with_and_without_rle() {
+        for width in [5, 40] {
+            let c = bright_canvas(width, 4);
+            let mut hdr = Vec::new();
+            c.write_hdr(&mut hdr).unwrap();
+            let read = Canvas::from_hdr(&hdr).unwrap();
+            assert_eq!((read.width, read.height), (width, 4));
+            for y in 0..4 {
+                for x in 0..width {
+                    assert!(close(c.pixel_at(x, y), read.pixel_at(x, y), 0.01));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hdr_rle_compresses_flat_rows() {
+        let mut c = Canvas::new(100, 1);
+        for x in 0..100 {
+            c.write_pixel(x, 0, Color::new(2.0, 2.0, 2.0));
+        }
+        let mut hdr = Vec::new();
+        c.write_hdr(&mut hdr).unwrap();
+        assert!(hdr.len() < 100);
+    }
+
+    #[test]
+    fn reading_old_style_hdr_runs() {
+        let mut hdr = b"#?RGBE\n\n-Y 1 +X 4\n".to_vec();
+        hdr.extend_from_slice(&[128, 64, 0, 129, 1, 1, 1, 3]);
+        let c = Canvas::from_hdr(&hdr).unwrap();
+        for x in 0..4 {
+            assert!(close(c.pixel_at(x, 0), Color::new(1.0, 0.5, 0.0), 0.01));
+        }
+    }
+
+    #[test]
+    fn reading_malformed_old_style_runs() {
+        let mut hdr = b"#?RGBE\n\n-Y 1 +X 4\n".to_vec();
+        hdr.extend_from_slice(&[128, 64, 0, 129]);
+        for _ in 0..9 {
+            hdr.extend_from_slice(&[1, 1, 1, 0]);
+        }
+        assert!(matches!(Canvas::from_hdr(&hdr), Err(ImageError::Malformed(_))));
+        let mut hdr = b"#?RGBE\n\n-Y 1 +X 4\n".to_vec();
+        hdr.extend_from_slice(&[128, 64, 0, 129, 1, 1, 1, 1, 1, 1, 1, 1]);
+        assert!(matches!(Canvas::from_hdr(&hdr), Err(ImageError::Malformed(_))));
+    }
+
+    #[test]
+    fn reading_invalid_hdr() {
+        assert!(matches!(Canvas::from_hdr(b"P6\n"), Err(ImageError::UnsupportedFormat(_))));
+        assert!(matches!(Canvas::from_hdr(b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n"), Err(ImageError::UnsupportedFormat(_))));
+        assert!(matches!(Canvas::from_hdr(b"#?RADIANCE\n\n+Y 1 +X 1\n\0\0\0\0"), Err(ImageError::UnsupportedFormat(_))));
+        assert!(matches!(Canvas::from_hdr(b"#?RADIANCE\n\n-Y 2 +X 1\n\0\0\0\0"), Err(ImageError::Truncated(_))));
+        assert!(matches!(Canvas::from_pfm(b"PF\n2 2\n-1.0\n\0\0\0\0"), Err(ImageError::Truncated(_))));
+    }
+}
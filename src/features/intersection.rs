@@ -0,0 +1,196 @@
+use super::{ray::Ray, shape::Shape, tuple::Tuple};
+
+// How far hit points are nudged off a surface before casting secondary rays, so
+// they do not hit the surface they start on.
+pub const SURFACE_OFFSET: f64 = 1e-5;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Intersection<'a> {
+    pub t: f64,
+    pub object: &'a Shape,
+}
+
+// Everything shading needs to know about a hit, computed once.
+#[derive(Debug, Clone, Copy)]
+pub struct Computations<'a> {
+    pub t: f64,
+    pub object: &'a Shape,
+    pub point: Tuple,
+    pub over_point: Tuple,
+    pub under_point: Tuple,
+    pub eyev: Tuple,
+    pub normalv: Tuple,
+    pub reflectv: Tuple,
+    pub inside: bool,
+    pub n1: f64,
+    pub n2: f64,
+}
+
+impl<'a> Intersection<'a> {
+    pub fn new(t: f64, object: &'a Shape) -> Self {
+        Self { t, object }
+    }
+
+    // `intersections` must be sorted by t; it is used to find the refractive indices
+    // on either side of the hit.
+    pub fn prepare_computations(&self, ray: &Ray, intersections: &[Intersection<'a>]) -> Computations<'a> {
+        let point = ray.position(self.t);
+        let eyev = -ray.direction;
+        let outward = self.object.normal_at(point);
+        let inside = outward.dot(&eyev) < 0.0;
+        let geometric = if inside { -outward } else { outward };
+        // Bumps only change how the surface is shaded; secondary rays still start
+        // off the real surface.
+        let normalv = match &self.object.material.bump {
+            Some(bump) => {
+                let perturbed = bump.perturb(self.object, point, outward);
+                if inside {
+                    -perturbed
+                } else {
+                    perturbed
+                }
+            }
+            None => geometric,
+        };
+
+        let (mut n1, mut n2) = (1.0, 1.0);
+        let mut containers: Vec<&Shape> = Vec::new();
+        for intersection in intersections {
+            let is_hit = std::ptr::eq(intersection.object, self.object) && intersection.t == self.t;
+            if is_hit {
+                n1 = containers.last().map_or(1.0, |object| object.material.refractive_index);
+            }
+            match containers.iter().position(|object| std::ptr::eq(*object, intersection.object)) {
+                Some(index) => {
+                    containers.remove(index);
+                }
+                None => containers.push(intersection.object),
+            }
+            if is_hit {
+                n2 = containers.last().map_or(1.0, |object| object.material.refractive_index);
+                break;
+            }
+        }
+
+        Computations {
+            t: self.t,
+            object: self.object,
+            point,
+            over_point: point + geometric * SURFACE_OFFSET,
+            under_point: point - geometric * SURFACE_OFFSET,
+            eyev,
+            normalv,
+            reflectv: ray.direction.reflect(&normalv),
+            inside,
+            n1,
+            n2,
+        }
+    }
+}
+
+// Whether an intersection at `t` counts as a hit, i.e. is not behind the ray's origin.
+pub fn in_front(t: f64) -> bool {
+    t >= 0.0
+}
+
+// The closest intersection in front of the ray's origin.
+pub fn hit<'a, 'b>(intersections: &'b [Intersection<'a>]) -> Option<&'b Intersection<'a>> {
+    intersections.iter().filter(|intersection| in_front(intersection.t)).min_by(|a, b| a.t.total_cmp(&b.t))
+}
+
+// Schlick's approximation of the Fresnel reflectance at a hit.
+pub fn schlick(comps: &Computations) -> f64 {
+    let mut cos = comps.eyev.dot(&comps.normalv);
+    if comps.n1 > comps.n2 {
+        let ratio = comps.n1 / comps.n2;
+        let sin2_t = ratio * ratio * (1.0 - cos * cos);
+        if sin2_t > 1.0 {
+            return 1.0;
+        }
+        cos = (1.0 - sin2_t).sqrt();
+    }
+    let r0 = ((comps.n1 - comps.n2) / (comps.n1 + comps.n2)).powi(2);
+    r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::{bump::Bump, matrix::Matrix, shape::ShapeKind};
+
+    fn glass_sphere() -> Shape {
+        let mut sphere = Shape::new(ShapeKind::Sphere);
+        sphere.material.transparency = 1.0;
+        sphere.material.refractive_index = 1.5;
+        sphere
+    }
+
+    #[test]
+    fn the_hit_is_the_lowest_nonnegative_intersection() {
+        let sphere = Shape::new(ShapeKind::Sphere);
+        let intersections = [Intersection::new(5.0, &sphere), Intersection::new(7.0, &sphere), Intersection::new(-3.0, &sphere), Intersection::new(2.0, &sphere)];
+        assert_eq!(hit(&intersections).unwrap().t, 2.0);
+        assert!(hit(&intersections[2..3]).is_none());
+        assert_eq!(hit(&[Intersection::new(0.0, &sphere)]).unwrap().t, 0.0);
+        assert!(in_front(0.0) && !in_front(-1e-9));
+    }
+
+    #[test]
+    fn hits_from_inside_flip_the_normal() {
+        let sphere = Shape::new(ShapeKind::Sphere);
+        let ray = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        let intersection = Intersection::new(1.0, &sphere);
+        let comps = intersection.prepare_computations(&ray, &[intersection]);
+        assert!(comps.inside);
+        assert_eq!(comps.point, Tuple::point(0.0, 0.0, 1.0));
+        assert_eq!(comps.normalv, Tuple::vector(0.0, 0.0, -1.0));
+        assert!(comps.over_point.z < comps.point.z && comps.under_point.z > comps.point.z);
+    }
+
+    #[test]
+    fn bumps_tilt_the_shading_normal_only() {
+        let mut sphere = Shape::new(ShapeKind::Sphere);
+        sphere.material.bump = Some(Bump::Noise { scale: 5.0, strength: 0.05 });
+        let ray = Ray::new(Tuple::point(0.3, 0.2, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let ts = sphere.intersect(&ray);
+        let intersections: Vec<Intersection> = ts.iter().map(|&t| Intersection::new(t, &sphere)).collect();
+        let comps = intersections[0].prepare_computations(&ray, &intersections);
+        let geometric = sphere.normal_at(comps.point);
+        assert!(comps.normalv != geometric && comps.normalv.dot(&geometric) > 0.9);
+        assert_eq!(comps.reflectv, ray.direction.reflect(&comps.normalv));
+        assert_eq!(comps.over_point, comps.point + geometric * SURFACE_OFFSET);
+    }
+
+    #[test]
+    fn finding_n1_and_n2_at_various_intersections() {
+        let mut a = glass_sphere();
+        a.set_transform(Matrix::scaling(2.0, 2.0, 2.0)).unwrap();
+        let mut b = glass_sphere();
+        b.set_transform(Matrix::translation(0.0, 0.0, -0.25)).unwrap();
+        b.material.refractive_index = 2.0;
+        let mut c = glass_sphere();
+        c.set_transform(Matrix::translation(0.0, 0.0, 0.25)).unwrap();
+        c.material.refractive_index = 2.5;
+
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -4.0), Tuple::vector(0.0, 0.0, 1.0));
+        let intersections = [(2.0, &a), (2.75, &b), (3.25, &c), (4.75, &b), (5.25, &c), (6.0, &a)].map(|(t, object)| Intersection::new(t, object));
+        let expected = [(1.0, 1.5), (1.5, 2.0), (2.0, 2.5), (2.5, 2.5), (2.5, 1.5), (1.5, 1.0)];
+        for (intersection, (n1, n2)) in intersections.iter().zip(expected) {
+            let comps = intersection.prepare_computations(&ray, &intersections);
+            assert_eq!((comps.n1, comps.n2), (n1, n2));
+        }
+    }
+
+    #[test]
+    fn schlick_approximation() {
+        let sphere = glass_sphere();
+        let half = 2f64.sqrt() / 2.0;
+        let ray = Ray::new(Tuple::point(0.0, 0.0, half), Tuple::vector(0.0, 1.0, 0.0));
+        let intersections = [Intersection::new(-half, &sphere), Intersection::new(half, &sphere)];
+        assert_eq!(schlick(&intersections[1].prepare_computations(&ray, &intersections)), 1.0);
+
+        let ray = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 1.0, 0.0));
+        let intersections = [Intersection::new(-1.0, &sphere), Intersection::new(1.0, &sphere)];
+        assert!((schlick(&intersections[1].prepare_computations(&ray, &intersections)) - 0.04).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,267 @@
+use super::{color::Color, random::Rng, tuple::Tuple};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointLight {
+    pub position: Tuple,
+    pub intensity: Color,
+}
+
+impl PointLight {
+    pub fn new(position: Tuple, intensity: Color) -> Self {
+        Self { position, intensity }
+    }
+}
+
+// A point light limited to a cone around `direction`. `cone_angle` is the half-angle
+// where the light ends, and it fades out smoothly over the last `falloff` radians.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpotLight {
+    pub position: Tuple,
+    pub direction: Tuple,
+    pub cone_angle: f64,
+    pub falloff: f64,
+    pub intensity: Color,
+}
+
+impl SpotLight {
+    pub fn new(position: Tuple, direction: Tuple, cone_angle: f64, falloff: f64, intensity: Color) -> Self {
+        Self { position, direction, cone_angle, falloff, intensity }
+    }
+
+    // How much of the light reaches along `lightv`, the direction from the light.
+    pub fn attenuation(&self, lightv: Tuple) -> f64 {
+        let angle = lightv.dot(&self.direction.normalize()).clamp(-1.0, 1.0).acos();
+        if angle >= self.cone_angle {
+            0.0
+        } else if angle <= self.cone_angle - self.falloff {
+            1.0
+        } else {
+            let t = (self.cone_angle - angle) / self.falloff;
+            t * t * (3.0 - 2.0 * t)
+        }
+    }
+}
+
+// A light so far away (the sun) that it reaches every point from the same
+// direction. `direction` is the way the light travels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DirectionalLight {
+    pub direction: Tuple,
+    pub intensity: Color,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Tuple, intensity: Color) -> Self {
+        Self { direction, intensity }
+    }
+}
+
+// A rectangle of light split into usteps x vsteps cells, with one sample taken
+// per cell. `uvec` and `vvec` are the size of a single cell, not the full edges.
+// Step counts below 1 are raised to 1; lighting averages over the samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AreaLight {
+    pub corner: Tuple,
+    pub uvec: Tuple,
+    pub usteps: usize,
+    pub vvec: Tuple,
+    pub vsteps: usize,
+    pub intensity: Color,
+    pub jitter: bool,
+}
+
+impl AreaLight {
+    pub fn new(corner: Tuple, full_uvec: Tuple, usteps: usize, full_vvec: Tuple, vsteps: usize, intensity: Color) -> Self {
+        let (usteps, vsteps) = (usteps.max(1), vsteps.max(1));
+        Self {
+            corner,
+            uvec: full_uvec / usteps as f64,
+            usteps,
+            vvec: full_vvec / vsteps as f64,
+            vsteps,
+            intensity,
+            jitter: true,
+        }
+    }
+
+    pub fn samples(&self) -> usize {
+        self.usteps * self.vsteps
+    }
+
+    pub fn position(&self) -> Tuple {
+        self.corner + self.uvec * (self.usteps as f64 / 2.0) + self.vvec * (self.vsteps as f64 / 2.0)
+    }
+
+    // Without jitter the sample sits in the middle of its cell.
+    pub fn point_on_light(&self, u: usize, v: usize, rng: &mut Rng) -> Tuple {
+        let (du, dv) = if self.jitter { (rng.next_f64(), rng.next_f64()) } else { (0.5, 0.5) };
+        self.corner + self.uvec * (u as f64 + du) + self.vvec * (v as f64 + dv)
+    }
+}
+
+// What one sample of a light looks like from a shading point: the unit vector
+// towards it, how far away it is (infinite for directional lights) and the
+// intensity arriving from it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightSample {
+    pub direction: Tuple,
+    pub distance: f64,
+    pub intensity: Color,
+}
+
+impl LightSample {
+    fn towards(position: Tuple, point: Tuple, intensity: Color) -> Self {
+        let to_light = position - point;
+        Self { direction: to_light.normalize(), distance: to_light.magnitude(), intensity }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Light {
+    Point(PointLight),
+    Spot(SpotLight),
+    Directional(DirectionalLight),
+    Area(AreaLight),
+}
+
+impl Light {
+    pub fn intensity(&self) -> Color {
+        match self {
+            Light::Point(light) => light.intensity,
+            Light::Spot(light) => light.intensity,
+            Light::Directional(light) => light.intensity,
+            Light::Area(light) => light.intensity,
+        }
+    }
+
+    // The samples that `point` is shaded against; every light but an area light has
+    // just one. Area light jitter is seeded from the shading point, so the shadow
+    // test and the lighting see the same samples and a render comes out the same on
+    // any number of threads.
+    pub fn samples(&self, point: Tuple) -> Vec<LightSample> {
+        match self {
+            Light::Point(light) => vec![LightSample::towards(light.position, point, light.intensity)],
+            Light::Spot(light) => {
+                let sample = LightSample::towards(light.position, point, light.intensity);
+                vec![LightSample { intensity: light.intensity * light.attenuation(-sample.direction), ..sample }]
+            }
+            Light::Directional(light) => vec![LightSample { direction: -light.direction.normalize(), distance: f64::INFINITY, intensity: light.intensity }],
+            Light::Area(light) => {
+                let seed = point.x.to_bits() ^ point.y.to_bits().rotate_left(21) ^ point.z.to_bits().rotate_left(42);
+                let mut rng = Rng::new(seed);
+                (0..light.vsteps)
+                    .flat_map(|v| (0..light.usteps).map(move |u| (u, v)))
+                    .map(|(u, v)| LightSample::towards(light.point_on_light(u, v, &mut rng), point, light.intensity))
+                    .collect()
+            }
+        }
+    }
+}
+
+impl From<PointLight> for Light {
+    fn from(light: PointLight) -> Self {
+        Light::Point(light)
+    }
+}
+
+impl From<SpotLight> for Light {
+    fn from(light: SpotLight) -> Self {
+        Light::Spot(light)
+    }
+}
+
+impl From<DirectionalLight> for Light {
+    fn from(light: DirectionalLight) -> Self {
+        Light::Directional(light)
+    }
+}
+
+impl From<AreaLight> for Light {
+    fn from(light: AreaLight) -> Self {
+        Light::Area(light)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn area_light() -> AreaLight {
+        let corner = Tuple::point(0.0, 0.0, 0.0);
+        AreaLight::new(corner, Tuple::vector(2.0, 0.0, 0.0), 4, Tuple::vector(0.0, 0.0, 1.0), 2, Color::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn creating_an_area_light() {
+        let light = area_light();
+        assert_eq!(light.uvec, Tuple::vector(0.5, 0.0, 0.0));
+        assert_eq!(light.vvec, Tuple::vector(0.0, 0.0, 0.5));
+        assert_eq!(light.samples(), 8);
+        assert_eq!(light.position(), Tuple::point(1.0, 0.0, 0.5));
+    }
+
+    #[test]
+    fn area_lights_take_at_least_one_step() {
+        let light = AreaLight::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(2.0, 0.0, 0.0), 0, Tuple::vector(0.0, 0.0, 1.0), 2, Color::new(1.0, 1.0, 1.0));
+        assert_eq!((light.usteps, light.vsteps, light.samples()), (1, 2, 2));
+        assert_eq!(light.uvec, Tuple::vector(2.0, 0.0, 0.0));
+        assert_eq!(light.position(), Tuple::point(1.0, 0.0, 0.5));
+    }
+
+    #[test]
+    fn finding_points_on_an_area_light() {
+        let mut light = area_light();
+        light.jitter = false;
+        let mut rng = Rng::new(0);
+        assert_eq!(light.point_on_light(0, 0, &mut rng), Tuple::point(0.25, 0.0, 0.25));
+        assert_eq!(light.point_on_light(1, 0, &mut rng), Tuple::point(0.75, 0.0, 0.25));
+        assert_eq!(light.point_on_light(3, 1, &mut rng), Tuple::point(1.75, 0.0, 0.75));
+    }
+
+    #[test]
+    fn jittered_samples_stay_in_their_cells() {
+        let light = Light::Area(area_light());
+        let point = Tuple::point(0.0, 5.0, 0.0);
+        let samples = light.samples(point);
+        assert_eq!(samples.len(), 8);
+        assert_eq!(samples, light.samples(point));
+        for (index, sample) in samples.iter().enumerate() {
+            let sample = point + sample.direction * sample.distance;
+            let (u, v) = ((index % 4) as f64, (index / 4) as f64);
+            assert!(sample.x > u * 0.5 - 1e-9 && sample.x < (u + 1.0) * 0.5 + 1e-9);
+            assert!(sample.z > v * 0.5 - 1e-9 && sample.z < (v + 1.0) * 0.5 + 1e-9);
+        }
+    }
+
+    #[test]
+    fn point_light_samples() {
+        let light = Light::Point(PointLight::new(Tuple::point(0.0, 10.0, 0.0), Color::new(1.0, 1.0, 1.0)));
+        let sample = light.samples(Tuple::point(0.0, 0.0, 0.0))[0];
+        assert_eq!(sample.direction, Tuple::vector(0.0, 1.0, 0.0));
+        assert_eq!(sample.distance, 10.0);
+        assert_eq!(sample.intensity, Color::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn spot_lights_fade_towards_the_edge_of_their_cone() {
+        let down = Tuple::vector(0.0, -1.0, 0.0);
+        let light = Light::Spot(SpotLight::new(Tuple::point(0.0, 1.0, 0.0), down, PI / 4.0, PI / 8.0, Color::new(1.0, 1.0, 1.0)));
+        let at = |x: f64| light.samples(Tuple::point(x, 0.0, 0.0))[0].intensity.red;
+        assert_eq!(at(0.0), 1.0);
+        assert_eq!(at(0.35), 1.0);
+        let halfway = at((3.0 * PI / 16.0).tan());
+        assert!((halfway - 0.5).abs() < 1e-9);
+        assert_eq!(at(1.5), 0.0);
+    }
+
+    #[test]
+    fn directional_lights_come_from_infinitely_far_away() {
+        let light = Light::Directional(DirectionalLight::new(Tuple::vector(0.0, -2.0, 0.0), Color::new(1.0, 1.0, 1.0)));
+        for point in [Tuple::point(0.0, 0.0, 0.0), Tuple::point(100.0, -5.0, 3.0)] {
+            let sample = light.samples(point)[0];
+            assert_eq!(sample.direction, Tuple::vector(0.0, 1.0, 0.0));
+            assert_eq!(sample.distance, f64::INFINITY);
+        }
+    }
+}
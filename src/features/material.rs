@@ -0,0 +1,202 @@
+use super::{bump::Bump, color::Color, light::Light, microfacet::Microfacet, shape::Shape, texture::ImageTexture, tuple::Tuple};
+
+// How a surface reflects the light that reaches it. Phong uses the diffuse,
+// specular and shininess coefficients; a microfacet surface uses its own
+// parameters and the colour as its base colour.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Brdf {
+    Phong,
+    Microfacet(Microfacet),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+    pub color: Color,
+    pub ambient: f64,
+    pub diffuse: f64,
+    pub specular: f64,
+    pub shininess: f64,
+    pub reflective: f64,
+    pub transparency: f64,
+    pub refractive_index: f64,
+    // Light the surface gives off by itself, whether or not anything lights it.
+    pub emissive: Color,
+    pub brdf: Brdf,
+    // Replaces `color` wherever the texture covers the surface.
+    pub texture: Option<ImageTexture>,
+    // Tilts the shading normal before the surface is lit.
+    pub bump: Option<Bump>,
+}
+
+impl Material {
+    // The surface colour at a world space point on `object`.
+    pub fn color_at(&self, object: &Shape, point: Tuple) -> Color {
+        match &self.texture {
+            Some(texture) => texture.color_at(object.inverse() * point),
+            None => self.color,
+        }
+    }
+
+    // Phong reflection for one light. `intensity` is the fraction of the light that
+    // reaches the point (0 in full shadow, 1 fully lit), and area lights have their
+    // diffuse and specular terms averaged over every sample.
+    pub fn lighting(&self, object: &Shape, light: &Light, point: Tuple, eyev: Tuple, normalv: Tuple, intensity: f64) -> Color {
+        let color = self.color_at(object, point);
+        let ambient = color * light.intensity() * self.ambient;
+        if intensity == 0.0 {
+            return ambient;
+        }
+
+        let samples = light.samples(point);
+        let mut sum = Color::new(0.0, 0.0, 0.0);
+        for sample in &samples {
+            let lightv = sample.direction;
+            if let Brdf::Microfacet(surface) = self.brdf {
+                sum = sum + sample.intensity * surface.reflectance(color, normalv, lightv, eyev);
+                continue;
+            }
+            let light_dot_normal = lightv.dot(&normalv);
+            if light_dot_normal < 0.0 {
+                continue;
+            }
+            sum = sum + color * sample.intensity * self.diffuse * light_dot_normal;
+            let reflectv = (-lightv).reflect(&normalv);
+            let reflect_dot_eye = reflectv.dot(&eyev);
+            if reflect_dot_eye > 0.0 {
+                sum = sum + sample.intensity * self.specular * reflect_dot_eye.powf(self.shininess);
+            }
+        }
+        ambient + sum * (intensity / samples.len() as f64)
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            color: Color::new(1.0, 1.0, 1.0),
+            ambient: 0.1,
+            diffuse: 0.9,
+            specular: 0.9,
+            shininess: 200.0,
+            reflective: 0.0,
+            transparency: 0.0,
+            refractive_index: 1.0,
+            emissive: Color::new(0.0, 0.0, 0.0),
+            brdf: Brdf::Phong,
+            texture: None,
+            bump: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::{
+        canvas::Canvas,
+        light::{AreaLight, PointLight},
+        matrix::Matrix,
+        shape::ShapeKind,
+        texture::{TextureFilter, UvMapping},
+    };
+    use std::sync::Arc;
+    use std::f64::consts::FRAC_1_SQRT_2;
+
+    fn setup() -> (Material, Shape, Tuple) {
+        (Material::default(), Shape::new(ShapeKind::Sphere), Tuple::point(0.0, 0.0, 0.0))
+    }
+
+    #[test]
+    fn lighting_with_the_eye_between_light_and_surface() {
+        let (material, object, position) = setup();
+        let light = Light::Point(PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0)));
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        assert_eq!(material.lighting(&object, &light, position, eyev, normalv, 1.0), Color::new(1.9, 1.9, 1.9));
+    }
+
+    #[test]
+    fn lighting_with_the_light_behind_the_surface() {
+        let (material, object, position) = setup();
+        let light = Light::Point(PointLight::new(Tuple::point(0.0, 0.0, 10.0), Color::new(1.0, 1.0, 1.0)));
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        assert_eq!(material.lighting(&object, &light, position, eyev, normalv, 1.0), Color::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn lighting_with_the_eye_in_the_path_of_the_reflection() {
+        let (material, object, position) = setup();
+        let light = Light::Point(PointLight::new(Tuple::point(0.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0)));
+        let eyev = Tuple::vector(0.0, -(2f64.sqrt()) / 2.0, -(2f64.sqrt()) / 2.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let result = material.lighting(&object, &light, position, eyev, normalv, 1.0);
+        assert!((result.red - 1.6364).abs() < 1e-4);
+    }
+
+    #[test]
+    fn lighting_in_shadow() {
+        let (material, object, position) = setup();
+        let light = Light::Point(PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0)));
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        assert_eq!(material.lighting(&object, &light, position, eyev, normalv, 0.0), Color::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn lighting_uses_the_light_intensity() {
+        let object = Shape::new(ShapeKind::Sphere);
+        let material = Material { ambient: 0.1, diffuse: 0.9, specular: 0.0, ..Material::default() };
+        let light = Light::Point(PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0)));
+        let point = Tuple::point(0.0, 0.0, -1.0);
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        assert_eq!(material.lighting(&object, &light, point, eyev, normalv, 0.5), Color::new(0.55, 0.55, 0.55));
+    }
+
+    #[test]
+    fn lighting_samples_the_area_light() {
+        let object = Shape::new(ShapeKind::Sphere);
+        let corner = Tuple::point(-0.5, -0.5, -5.0);
+        let mut area = AreaLight::new(corner, Tuple::vector(1.0, 0.0, 0.0), 2, Tuple::vector(0.0, 1.0, 0.0), 2, Color::new(1.0, 1.0, 1.0));
+        area.jitter = false;
+        let light = Light::Area(area);
+        let material = Material { ambient: 0.1, diffuse: 0.9, specular: 0.0, ..Material::default() };
+        let eye = Tuple::point(0.0, 0.0, -5.0);
+        for (point, expected) in [(Tuple::point(0.0, 0.0, -1.0), 0.9965), (Tuple::point(0.0, FRAC_1_SQRT_2, -FRAC_1_SQRT_2), 0.62318)] {
+            let eyev = (eye - point).normalize();
+            let normalv = Tuple::vector(point.x, point.y, point.z);
+            let result = material.lighting(&object, &light, point, eyev, normalv, 1.0);
+            assert!((result.red - expected).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn lighting_a_microfacet_surface() {
+        let object = Shape::new(ShapeKind::Sphere);
+        let light = Light::Point(PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0)));
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let rough = Material { ambient: 0.0, brdf: Brdf::Microfacet(Microfacet::new(1.0, 0.0)), ..Material::default() };
+        let head_on = rough.lighting(&object, &light, Tuple::point(0.0, 0.0, 0.0), normalv, normalv, 1.0);
+        assert!(head_on.red > 0.9 && head_on.red < 1.1);
+
+        let glancing = Tuple::vector(0.0, FRAC_1_SQRT_2, -FRAC_1_SQRT_2);
+        let shiny = Material { brdf: Brdf::Microfacet(Microfacet::new(0.2, 0.0)), ..rough };
+        let off_highlight = shiny.lighting(&object, &light, Tuple::point(0.0, 0.0, 0.0), glancing, normalv, 1.0);
+        let on_highlight = shiny.lighting(&object, &light, Tuple::point(0.0, 0.0, 0.0), normalv, normalv, 1.0);
+        assert!(on_highlight.red > off_highlight.red * 2.0);
+    }
+
+    #[test]
+    fn textures_replace_the_material_color() {
+        let mut image = Canvas::new(2, 1);
+        image.write_pixel(0, 0, Color::new(1.0, 0.0, 0.0));
+        image.write_pixel(1, 0, Color::new(0.0, 0.0, 1.0));
+        let mut object = Shape::new(ShapeKind::Plane);
+        object.set_transform(Matrix::translation(0.0, 0.0, 10.0)).unwrap();
+        let material = Material { texture: Some(ImageTexture::new(Arc::new(image), UvMapping::Planar, TextureFilter::Nearest)), ..Material::default() };
+        assert_eq!(material.color_at(&object, Tuple::point(0.2, 0.0, 10.5)), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(material.color_at(&object, Tuple::point(0.8, 0.0, 10.5)), Color::new(0.0, 0.0, 1.0));
+        assert_eq!(Material::default().color_at(&object, Tuple::point(0.8, 0.0, 10.5)), Color::new(1.0, 1.0, 1.0));
+    }
+}
@@ -1,7 +1,7 @@
 use core::fmt;
 use std::ops::{Add, Sub, Mul};
 
-use super::{util::almost_equal, tuple::Tuple};
+use super::{error::{Error, Result}, util::almost_equal, tuple::Tuple};
 
 fn add_matrix (matrix1: &Matrix, matrix2: &Matrix, sign: bool) -> Matrix {
     let mut rows = Vec::new();
@@ -59,21 +59,32 @@ impl Matrix {
         Matrix::new(self.columns.clone())
     }
 
-    pub fn determinant(&self) -> f64 {
-        if self.rows.len() != self.rows[0].len() {
-            panic!("Matrix must be square");
-        } else
-        if self.rows.len() == 1 {
-            self.rows[0][0]
-        } else 
-        if self.rows.len() == 2 {
-            self.rows[0][0] * self.rows[1][1] - self.rows[0][1] * self.rows[1][0]
+    pub fn size(&self) -> (usize, usize) {
+        (self.rows.len(), self.columns.len())
+    }
+
+    fn check_square(&self) -> Result<()> {
+        let (rows, columns) = self.size();
+        if rows != columns || self.rows.iter().any(|row| row.len() != columns) {
+            return Err(Error::NotSquare { rows, columns });
+        }
+        Ok(())
+    }
+
+    pub fn determinant(&self) -> Result<f64> {
+        self.check_square()?;
+        if self.rows.is_empty() {
+            Ok(1.0)
+        } else if self.rows.len() == 1 {
+            Ok(self.rows[0][0])
+        } else if self.rows.len() == 2 {
+            Ok(self.rows[0][0] * self.rows[1][1] - self.rows[0][1] * self.rows[1][0])
         } else {
             let mut sum = 0.0;
             for i in 0..self.rows[0].len() {
-                sum += self.rows[0][i] * self.cofactor(0, i);
+                sum += self.rows[0][i] * self.cofactor(0, i)?;
             }
-            sum
+            Ok(sum)
         }
     }
 
@@ -95,39 +106,80 @@ impl Matrix {
         Matrix::new(rows)
     }
 
-    pub fn minor(&self, row: usize, column: usize) -> f64 {
+    pub fn minor(&self, row: usize, column: usize) -> Result<f64> {
         self.submatrix(row, column).determinant()
     }
 
-    pub fn cofactor(&self, row: usize, column: usize) -> f64 {
-        let minor = self.minor(row, column);
-        if (row + column) % 2 == 0 {
-            minor
+    pub fn cofactor(&self, row: usize, column: usize) -> Result<f64> {
+        let minor = self.minor(row, column)?;
+        if (row + column).is_multiple_of(2) {
+            Ok(minor)
         } else {
-            -minor
+            Ok(-minor)
         }
     }
 
     pub fn is_invertible(&self) -> bool {
-        self.determinant() != 0.0
+        self.determinant().is_ok_and(|det| det != 0.0)
     }
 
-    pub fn inverse(&self) -> Option<Self> {
-        let det = self.determinant();
+    pub fn inverse(&self) -> Result<Self> {
+        let det = self.determinant()?;
         if det == 0.0 {
-            None
-        } else {
-            let inv_det = 1.0 / det;
-            let mut rows = Vec::new();
-            for i in 0..self.rows.len() {
-                let mut row = Vec::new();
-                for j in 0..self.rows[i].len() {
-                    row.push(self.cofactor(j, i) * inv_det);
+            return Err(Error::NotInvertible);
+        }
+        let inv_det = 1.0 / det;
+        let mut rows = Vec::new();
+        for i in 0..self.rows.len() {
+            let mut row = Vec::new();
+            for j in 0..self.rows[i].len() {
+                row.push(self.cofactor(j, i)? * inv_det);
+            }
+            rows.push(row);
+        }
+        Ok(Matrix::new(rows))
+    }
+
+    pub fn checked_add(&self, other: &Self) -> Result<Self> {
+        if self.size() != other.size() {
+            return Err(Error::DimensionMismatch { left: self.size(), right: other.size() });
+        }
+        Ok(add_matrix(self, other, true))
+    }
+
+    pub fn checked_sub(&self, other: &Self) -> Result<Self> {
+        if self.size() != other.size() {
+            return Err(Error::DimensionMismatch { left: self.size(), right: other.size() });
+        }
+        Ok(add_matrix(self, other, false))
+    }
+
+    pub fn checked_mul(&self, other: &Self) -> Result<Self> {
+        if self.columns.len() != other.rows.len() {
+            return Err(Error::DimensionMismatch { left: self.size(), right: other.size() });
+        }
+        let mut rows = Vec::new();
+        for i in 0..self.rows.len() {
+            let mut row = Vec::new();
+            for j in 0..other.columns.len() {
+                let mut sum = 0.0;
+                for k in 0..self.rows[i].len() {
+                    sum += self.rows[i][k] * other.columns[j][k];
                 }
-                rows.push(row);
+                row.push(sum);
             }
-            Some(Matrix::new(rows))
+            rows.push(row);
+        }
+        Ok(Matrix::new(rows))
+    }
+
+    pub fn checked_mul_tuple(&self, tuple: Tuple) -> Result<Tuple> {
+        if self.size() != (4, 4) {
+            return Err(Error::DimensionMismatch { left: self.size(), right: (4, 1) });
         }
+        let values = [tuple.x, tuple.y, tuple.z, tuple.w];
+        let component = |i: usize| self.rows[i].iter().zip(values.iter()).map(|(a, b)| a * b).sum();
+        Ok(Tuple::new(component(0), component(1), component(2), component(3)))
     }
 
     pub fn translation(x: f64, y: f64, z: f64) -> Self {
@@ -185,13 +237,26 @@ impl Matrix {
         ])
     }
 
+    pub fn view_transform(from: Tuple, to: Tuple, up: Tuple) -> Self {
+        let forward = (to - from).normalize();
+        let left = forward.cross(&up.normalize());
+        let true_up = left.cross(&forward);
+        let orientation = Matrix::new(vec![
+            vec![left.x, left.y, left.z, 0.0],
+            vec![true_up.x, true_up.y, true_up.z, 0.0],
+            vec![-forward.x, -forward.y, -forward.z, 0.0],
+            vec![0.0, 0.0, 0.0, 1.0],
+        ]);
+        orientation * Matrix::translation(-from.x, -from.y, -from.z)
+    }
+
 }
 
 impl Add for Matrix {
     type Output = Self;
 
     fn add(self, other: Self) -> Self {
-        add_matrix(&self, &other, true)
+        self.checked_add(&other).unwrap()
     }
 }
 
@@ -199,27 +264,17 @@ impl Sub for Matrix {
     type Output = Self;
 
     fn sub(self, other: Self) -> Self {
-        add_matrix(&self, &other, false)
+        self.checked_sub(&other).unwrap()
     }
 }
 
+// The operators panic on mismatched dimensions; use the checked_* methods to get an
+// Error instead.
 impl Mul for Matrix {
     type Output = Self;
 
     fn mul(self, other: Self) -> Self {
-        let mut rows = Vec::new();
-        for i in 0..self.rows.len() {
-            let mut row = Vec::new();
-            for j in 0..other.columns.len() {
-                let mut sum = 0.0;
-                for k in 0..self.rows[i].len() {
-                    sum += self.rows[i][k] * other.columns[j][k];
-                }
-                row.push(sum);
-            }
-            rows.push(row);
-        }
-        Matrix::new(rows)
+        self.checked_mul(&other).unwrap()
     }
 }
 
@@ -241,11 +296,23 @@ impl Mul<Tuple> for Matrix {
     type Output = Tuple;
 
     fn mul(self, other: Tuple) -> Tuple {
-        let x = other.x * self.rows[0][0] + other.y * self.rows[0][1] + other.z * self.rows[0][2] + other.w * self.rows[0][3];
-        let y = other.x * self.rows[1][0] + other.y * self.rows[1][1] + other.z * self.rows[1][2] + other.w * self.rows[1][3];
-        let z = other.x * self.rows[2][0] + other.y * self.rows[2][1] + other.z * self.rows[2][2] + other.w * self.rows[2][3];
-        let w = other.x * self.rows[3][0] + other.y * self.rows[3][1] + other.z * self.rows[3][2] + other.w * self.rows[3][3];
-        Tuple::new(x, y, z, w)
+        self.checked_mul_tuple(other).unwrap()
+    }
+}
+
+impl Mul for &Matrix {
+    type Output = Matrix;
+
+    fn mul(self, other: Self) -> Matrix {
+        self.checked_mul(other).unwrap()
+    }
+}
+
+impl Mul<Tuple> for &Matrix {
+    type Output = Tuple;
+
+    fn mul(self, other: Tuple) -> Tuple {
+        self.checked_mul_tuple(other).unwrap()
     }
 }
 
@@ -255,7 +322,7 @@ impl fmt::Display for Matrix {
             for val in row {
                 write!(f, "{:.3}\t", val)?;
             }
-            write!(f, "\n")?;
+            writeln!(f)?;
         }
         Ok(())
     }
@@ -300,7 +367,7 @@ mod tests {
     #[test]
     fn determinant_matrix() {
         let matrix1 = Matrix::new(vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0], vec![7.0, 8.0, 9.0]]);
-        assert_eq!(matrix1.determinant(), 0.0);
+        assert_eq!(matrix1.determinant().unwrap(), 0.0);
     }
 
     #[test]
@@ -314,30 +381,50 @@ mod tests {
     fn minor_matrix() {
         let matrix1 = Matrix::new(vec![vec![3.0, 5.0, 0.0], vec![2.0, -1.0, -7.0], vec![6.0, -1.0, 5.0]]);
         let matrix2 = matrix1.submatrix(1, 0);
-        assert_eq!(matrix2.determinant(), 25.0);
-        assert_eq!(matrix1.minor(1, 0), 25.0);
+        assert_eq!(matrix2.determinant().unwrap(), 25.0);
+        assert_eq!(matrix1.minor(1, 0).unwrap(), 25.0);
     }
 
     #[test]
     fn cofactor_matrix() {
         let matrix1 = Matrix::new(vec![vec![3.0, 5.0, 0.0], vec![2.0, -1.0, -7.0], vec![6.0, -1.0, 5.0]]);
-        assert_eq!(matrix1.cofactor(0, 0), -12.0);
+        assert_eq!(matrix1.cofactor(0, 0).unwrap(), -12.0);
     }
 
     #[test]
     fn cofactor_matrix_2() {
         let matrix1 = Matrix::new(vec![vec![3.0, 5.0, 0.0], vec![2.0, -1.0, -7.0], vec![6.0, -1.0, 5.0]]);
-        assert_eq!(matrix1.cofactor(1, 0), -25.0);
+        assert_eq!(matrix1.cofactor(1, 0).unwrap(), -25.0);
     }
 
     #[test]
     fn inverse_matrix() {
         let a = Matrix::new(vec![vec![1.0, 2.0], vec![3.0, 4.0]]);
         let expected = Matrix::new(vec![vec![-2.0, 1.0], vec![1.5, -0.5]]);
-        assert_eq!(a.inverse(), Some(expected));
+        assert_eq!(a.inverse().unwrap(), expected);
     
         let b = Matrix::new(vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0], vec![7.0, 8.0, 9.0]]);
-        assert_eq!(b.inverse(), None);
+        assert!(matches!(b.inverse(), Err(Error::NotInvertible)));
+        assert!(!b.is_invertible());
+    }
+
+    #[test]
+    fn non_square_matrix_operations_return_errors() {
+        let matrix = Matrix::new(vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]]);
+        assert!(matches!(matrix.determinant(), Err(Error::NotSquare { rows: 2, columns: 3 })));
+        assert!(matches!(matrix.inverse(), Err(Error::NotSquare { .. })));
+        assert!(!matrix.is_invertible());
+    }
+
+    #[test]
+    fn mismatched_dimensions_return_errors() {
+        let a = Matrix::new(vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]]);
+        let b = Matrix::identity(2);
+        assert!(matches!(a.checked_add(&b), Err(Error::DimensionMismatch { left: (2, 3), right: (2, 2) })));
+        assert!(matches!(a.checked_sub(&b), Err(Error::DimensionMismatch { .. })));
+        assert!(matches!(a.checked_mul(&b), Err(Error::DimensionMismatch { .. })));
+        assert!(matches!(b.checked_mul_tuple(Tuple::point(1.0, 2.0, 3.0)), Err(Error::DimensionMismatch { .. })));
+        assert_eq!(b.checked_mul(&a).unwrap(), a);
     }
 
     #[test]
@@ -463,4 +550,13 @@ mod tests {
         let transform = translation * scaling * rotation;
         assert_eq!(transform * point, Tuple::point(15.0, 0.0, 7.0));
     }
+
+    #[test]
+    fn view_transformations() {
+        let (origin, up) = (Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 1.0, 0.0));
+        assert_eq!(Matrix::view_transform(origin, Tuple::point(0.0, 0.0, -1.0), up), Matrix::identity(4));
+        assert_eq!(Matrix::view_transform(origin, Tuple::point(0.0, 0.0, 1.0), up), Matrix::scaling(-1.0, 1.0, -1.0));
+        let from = Tuple::point(0.0, 0.0, 8.0);
+        assert_eq!(Matrix::view_transform(from, origin, up), Matrix::translation(0.0, 0.0, -8.0));
+    }
 }
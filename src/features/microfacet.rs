@@ -0,0 +1,167 @@
+use std::f64::consts::PI;
+
+use super::{color::Color, random::Rng, tuple::Tuple};
+
+// Roughness below this makes the GGX distribution a spike that no sample can find.
+const MIN_ALPHA: f64 = 1e-3;
+// Reflectance of common dielectrics at normal incidence.
+const DIELECTRIC_F0: f64 = 0.04;
+
+// Cook-Torrance reflection with the GGX (Trowbridge-Reitz) distribution, Smith
+// shadowing and Schlick's Fresnel. `roughness` and `metallic` run from 0 to 1, as
+// in most authoring tools, and the material's colour is the base colour: the
+// diffuse albedo of a dielectric or the specular tint of a metal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Microfacet {
+    pub roughness: f64,
+    pub metallic: f64,
+}
+
+impl Microfacet {
+    pub fn new(roughness: f64, metallic: f64) -> Self {
+        Self { roughness, metallic }
+    }
+
+    fn alpha(&self) -> f64 {
+        (self.roughness * self.roughness).max(MIN_ALPHA)
+    }
+
+    fn distribution(&self, n_dot_h: f64) -> f64 {
+        let alpha2 = self.alpha() * self.alpha();
+        let denominator = n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0;
+        alpha2 / (PI * denominator * denominator)
+    }
+
+    fn geometry(&self, n_dot_l: f64, n_dot_v: f64) -> f64 {
+        let k = self.alpha() / 2.0;
+        let g1 = |cosine: f64| cosine / (cosine * (1.0 - k) + k);
+        g1(n_dot_l) * g1(n_dot_v)
+    }
+
+    fn fresnel(&self, base: Color, v_dot_h: f64) -> Color {
+        let dielectric = Color::new(DIELECTRIC_F0, DIELECTRIC_F0, DIELECTRIC_F0);
+        let f0 = dielectric * (1.0 - self.metallic) + base * self.metallic;
+        let white = Color::new(1.0, 1.0, 1.0);
+        f0 + (white - f0) * (1.0 - v_dot_h).powi(5)
+    }
+
+    // The BRDF for light arriving along `lightv` and leaving along `eyev`. The
+    // diffuse lobe only gets the light that Fresnel reflection at the viewing angle
+    // leaves over, so the two lobes together never reflect more than arrives.
+    pub fn brdf(&self, base: Color, normalv: Tuple, lightv: Tuple, eyev: Tuple) -> Color {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let (n_dot_l, n_dot_v) = (normalv.dot(&lightv), normalv.dot(&eyev));
+        if n_dot_l <= 0.0 || n_dot_v <= 0.0 {
+            return black;
+        }
+        let halfway = (lightv + eyev).normalize();
+        let fresnel = self.fresnel(base, eyev.dot(&halfway).max(0.0));
+        let specular = fresnel * (self.distribution(normalv.dot(&halfway).max(0.0)) * self.geometry(n_dot_l, n_dot_v) / (4.0 * n_dot_l * n_dot_v));
+        let white = Color::new(1.0, 1.0, 1.0);
+        let diffuse = (white - self.fresnel(base, n_dot_v)) * base * ((1.0 - self.metallic) / PI);
+        diffuse + specular
+    }
+
+    // What the Phong `lighting` adds per unit of light intensity: the BRDF times the
+    // cosine, scaled by π so a rough white dielectric lit head-on reflects about as
+    // much as a Phong surface with a diffuse of 1.
+    pub fn reflectance(&self, base: Color, normalv: Tuple, lightv: Tuple, eyev: Tuple) -> Color {
+        self.brdf(base, normalv, lightv, eyev) * (PI * normalv.dot(&lightv).max(0.0))
+    }
+
+    // Picks a direction to continue a path in, returning it with the path weight
+    // brdf * cos / pdf. Half of the samples follow the GGX distribution of normals and
+    // half a cosine-weighted hemisphere; weighting by the combined pdf keeps the
+    // estimate unbiased and neither lobe starved of samples.
+    pub fn sample(&self, base: Color, normalv: Tuple, eyev: Tuple, rng: &mut Rng) -> Option<(Tuple, Color)> {
+        let (tangent, bitangent) = normalv.tangent_frame();
+        let (u1, u2, phi) = (rng.next_f64(), rng.next_f64(), 2.0 * PI * rng.next_f64());
+        let lightv = if u1 < 0.5 {
+            let alpha2 = self.alpha() * self.alpha();
+            let cos_theta = ((1.0 - u2) / (1.0 + (alpha2 - 1.0) * u2)).sqrt();
+            let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+            let halfway = tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + normalv * cos_theta;
+            (-eyev).reflect(&halfway)
+        } else {
+            let r = u2.sqrt();
+            tangent * (r * phi.cos()) + bitangent * (r * phi.sin()) + normalv * (1.0 - u2).sqrt()
+        };
+
+        let n_dot_l = normalv.dot(&lightv);
+        if n_dot_l <= 0.0 {
+            return None;
+        }
+        let pdf = 0.5 * self.specular_pdf(normalv, lightv, eyev) + 0.5 * n_dot_l / PI;
+        Some((lightv, self.brdf(base, normalv, lightv, eyev) * (n_dot_l / pdf)))
+    }
+
+    fn specular_pdf(&self, normalv: Tuple, lightv: Tuple, eyev: Tuple) -> f64 {
+        let halfway = (lightv + eyev).normalize();
+        let n_dot_h = normalv.dot(&halfway).max(0.0);
+        let v_dot_h = eyev.dot(&halfway);
+        if v_dot_h <= 0.0 {
+            return 0.0;
+        }
+        self.distribution(n_dot_h) * n_dot_h / (4.0 * v_dot_h)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(x: f64, y: f64, z: f64) -> Tuple {
+        Tuple::vector(x, y, z).normalize()
+    }
+
+    #[test]
+    fn the_distribution_integrates_to_one() {
+        // ∫ D(h) cos θh dω over the hemisphere is 1 for any roughness.
+        for roughness in [0.3, 0.6, 1.0] {
+            let surface = Microfacet::new(roughness, 0.0);
+            let steps = 4000;
+            let integral: f64 = (0..steps)
+                .map(|i| {
+                    let theta = (i as f64 + 0.5) / steps as f64 * PI / 2.0;
+                    surface.distribution(theta.cos()) * theta.cos() * theta.sin() * 2.0 * PI * (PI / 2.0 / steps as f64)
+                })
+                .sum();
+            assert!((integral - 1.0).abs() < 1e-3, "roughness {} integrates to {}", roughness, integral);
+        }
+    }
+
+    #[test]
+    fn surfaces_do_not_reflect_more_than_they_receive() {
+        // Monte Carlo estimate of the directional albedo, which must stay at or below 1.
+        let normal = Tuple::vector(0.0, 1.0, 0.0);
+        let white = Color::new(1.0, 1.0, 1.0);
+        for (roughness, metallic) in [(0.2, 0.0), (0.5, 0.0), (1.0, 0.0), (0.3, 1.0), (0.8, 0.5)] {
+            let surface = Microfacet::new(roughness, metallic);
+            for eyev in [normal, unit(1.0, 1.0, 0.0), unit(3.0, 1.0, 0.0)] {
+                let mut rng = Rng::new(5);
+                let albedo = (0..20000).filter_map(|_| surface.sample(white, normal, eyev, &mut rng)).map(|(_, weight)| weight.red).sum::<f64>() / 20000.0;
+                assert!(albedo <= 1.02, "roughness {} metallic {} reflects {}", roughness, metallic, albedo);
+                assert!(albedo > 0.4);
+            }
+        }
+    }
+
+    #[test]
+    fn metals_tint_their_highlights() {
+        let gold = Color::new(1.0, 0.8, 0.3);
+        let normal = Tuple::vector(0.0, 0.0, -1.0);
+        let metal = Microfacet::new(0.3, 1.0).reflectance(gold, normal, normal, normal);
+        assert!(metal.red > metal.green && metal.green > metal.blue);
+        let plastic = Microfacet::new(0.3, 0.0).brdf(gold, normal, unit(1.0, 0.0, -1.0), unit(-1.0, 0.0, -1.0));
+        let specular = Microfacet::new(0.3, 0.0).brdf(Color::new(0.0, 0.0, 0.0), normal, unit(1.0, 0.0, -1.0), unit(-1.0, 0.0, -1.0));
+        assert!(plastic.red > plastic.blue && specular.red == specular.blue);
+    }
+
+    #[test]
+    fn no_light_reaches_from_below_the_surface() {
+        let surface = Microfacet::new(0.5, 0.0);
+        let normal = Tuple::vector(0.0, 1.0, 0.0);
+        let below = unit(0.0, -1.0, 1.0);
+        assert_eq!(surface.reflectance(Color::new(1.0, 1.0, 1.0), normal, below, normal), Color::new(0.0, 0.0, 0.0));
+    }
+}
@@ -3,3 +3,27 @@ pub mod color;
 pub mod util;
 pub mod canvas;
 pub mod matrix;
+pub mod render;
+pub mod random;
+pub mod sampling;
+pub mod png;
+pub mod hdr;
+pub mod tone;
+pub mod error;
+pub mod draw;
+pub mod filter;
+pub mod compare;
+pub mod yaml;
+pub mod scene;
+pub mod ray;
+pub mod light;
+pub mod texture;
+pub mod bump;
+pub mod material;
+pub mod microfacet;
+pub mod shape;
+pub mod intersection;
+pub mod world;
+pub mod camera;
+pub mod path;
+pub mod stats;
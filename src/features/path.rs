@@ -0,0 +1,220 @@
+use std::f64::consts::PI;
+
+use super::{
+    color::Color,
+    intersection::{hit, schlick, Computations},
+    material::Brdf,
+    random::Rng,
+    ray::Ray,
+    stats::RayKind,
+    tuple::Tuple,
+    world::World,
+};
+
+// Paths shorter than this are never cut short by Russian roulette.
+const MIN_BOUNCES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Integrator {
+    // The recursive tracer from the book: direct light, mirror reflection and refraction.
+    Whitted,
+    // Unbiased Monte Carlo path tracing, which adds indirect light bounced off diffuse surfaces.
+    PathTracing,
+}
+
+impl Integrator {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "whitted" => Some(Integrator::Whitted),
+            "path" => Some(Integrator::PathTracing),
+            _ => None,
+        }
+    }
+}
+
+// A direction in the hemisphere around `normal`, more likely the closer it is to
+// the normal (pdf cos θ / π), which cancels the cosine term of a diffuse surface.
+pub fn cosine_hemisphere(normal: Tuple, rng: &mut Rng) -> Tuple {
+    let (tangent, bitangent) = normal.tangent_frame();
+    let (phi, r2) = (2.0 * PI * rng.next_f64(), rng.next_f64());
+    let r = r2.sqrt();
+    tangent * (r * phi.cos()) + bitangent * (r * phi.sin()) + normal * (1.0 - r2).sqrt()
+}
+
+impl World {
+    // One path's estimate of the light arriving along `ray`, following at most
+    // `max_bounces` surface interactions. Each bounce picks one of the diffuse,
+    // reflected and refracted lobes at random, weighted the way `shade_hit` weighs
+    // them, and diffuse bounces add the lights directly (light intensities mean the
+    // same as in the Whitted tracer). Emissive surfaces light the scene wherever a
+    // path happens to hit them.
+    pub fn trace_path(&self, ray: &Ray, max_bounces: usize, rng: &mut Rng) -> Color {
+        let mut radiance = Color::new(0.0, 0.0, 0.0);
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut ray = *ray;
+        for bounce in 0..max_bounces {
+            let intersections = self.intersect(&ray);
+            let Some(hit) = hit(&intersections) else { break };
+            let comps = hit.prepare_computations(&ray, &intersections);
+            let material = &comps.object.material;
+            radiance = radiance + throughput * material.emissive;
+
+            let (reflect, refract) = self.lobe_weights(&comps);
+            let total = 1.0 + reflect + refract;
+            let choice = rng.next_f64() * total;
+            throughput = throughput * total;
+            let (origin, direction, kind) = if choice < 1.0 {
+                let base = material.color_at(comps.object, comps.point);
+                let direction = match material.brdf {
+                    Brdf::Phong => {
+                        let albedo = base * material.diffuse;
+                        radiance = radiance + throughput * albedo * self.direct_light(&comps, |lightv| Color::new(1.0, 1.0, 1.0) * lightv.dot(&comps.normalv).max(0.0));
+                        throughput = throughput * albedo;
+                        cosine_hemisphere(comps.normalv, rng)
+                    }
+                    Brdf::Microfacet(surface) => {
+                        let reflectance = |lightv: Tuple| surface.reflectance(base, comps.normalv, lightv, comps.eyev);
+                        radiance = radiance + throughput * self.direct_light(&comps, reflectance);
+                        let Some((direction, weight)) = surface.sample(base, comps.normalv, comps.eyev, rng) else { break };
+                        throughput = throughput * weight;
+                        direction
+                    }
+                };
+                (comps.over_point, direction, RayKind::Diffuse)
+            } else if choice < 1.0 + reflect {
+                (comps.over_point, comps.reflectv, RayKind::Reflection)
+            } else {
+                match refraction_direction(&comps) {
+                    Some(direction) => (comps.under_point, direction, RayKind::Refraction),
+                    None => (comps.over_point, comps.reflectv, RayKind::Reflection),
+                }
+            };
+
+            if bounce + 1 >= MIN_BOUNCES {
+                let survival = throughput.red.max(throughput.green).max(throughput.blue).min(0.95);
+                if rng.next_f64() >= survival {
+                    break;
+                }
+                throughput = throughput * (1.0 / survival);
+            }
+            self.counters.count_ray(kind);
+            ray = Ray::new(origin, direction);
+        }
+        radiance
+    }
+
+    // The reflected and refracted weights relative to the diffuse lobe's weight of 1.
+    fn lobe_weights(&self, comps: &Computations) -> (f64, f64) {
+        let material = &comps.object.material;
+        if material.reflective > 0.0 && material.transparency > 0.0 {
+            let reflectance = schlick(comps);
+            (material.reflective * reflectance, material.transparency * (1.0 - reflectance))
+        } else {
+            (material.reflective, material.transparency)
+        }
+    }
+
+    // The light reaching the point and reflected towards the eye, where `response`
+    // gives how much of a unit of light arriving along a direction is reflected.
+    fn direct_light<R: Fn(Tuple) -> Color>(&self, comps: &Computations, response: R) -> Color {
+        self.lights.iter().fold(Color::new(0.0, 0.0, 0.0), |sum, light| {
+            let visible = self.intensity_at(light, comps.over_point);
+            let samples = light.samples(comps.over_point);
+            let arriving = samples.iter().fold(Color::new(0.0, 0.0, 0.0), |sum, sample| sum + sample.intensity * response(sample.direction));
+            sum + arriving * (visible / samples.len() as f64)
+        })
+    }
+}
+
+fn refraction_direction(comps: &Computations) -> Option<Tuple> {
+    let ratio = comps.n1 / comps.n2;
+    let cos_i = comps.eyev.dot(&comps.normalv);
+    let sin2_t = ratio * ratio * (1.0 - cos_i * cos_i);
+    if sin2_t > 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(comps.normalv * (ratio * cos_i - cos_t) - comps.eyev * ratio)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::{
+        light::{Light, PointLight},
+        shape::{Shape, ShapeKind},
+    };
+
+    #[test]
+    fn hemisphere_samples_face_the_normal() {
+        let mut rng = Rng::new(7);
+        let normal = Tuple::vector(0.0, 0.0, -1.0);
+        let mut mean_cosine = 0.0;
+        for _ in 0..10000 {
+            let direction = cosine_hemisphere(normal, &mut rng);
+            assert!((direction.magnitude() - 1.0).abs() < 1e-9);
+            assert!(direction.dot(&normal) >= 0.0);
+            mean_cosine += direction.dot(&normal) / 10000.0;
+        }
+        // E[cos θ] under a cosine-weighted distribution is 2/3.
+        assert!((mean_cosine - 2.0 / 3.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn paths_pick_up_emitted_light() {
+        let mut lamp = Shape::new(ShapeKind::Sphere);
+        lamp.material.emissive = Color::new(2.0, 1.0, 0.5);
+        lamp.material.diffuse = 0.0;
+        let world = World { objects: vec![lamp], ..World::default() };
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(world.trace_path(&ray, 5, &mut Rng::new(1)), Color::new(2.0, 1.0, 0.5));
+        let miss = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 1.0, 0.0));
+        assert_eq!(world.trace_path(&miss, 5, &mut Rng::new(1)), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn a_single_bounce_matches_direct_diffuse_light() {
+        let mut floor = Shape::new(ShapeKind::Plane);
+        floor.material.color = Color::new(0.5, 0.5, 0.5);
+        floor.material.diffuse = 1.0;
+        let light = Light::Point(PointLight::new(Tuple::point(0.0, 10.0, 0.0), Color::new(1.0, 1.0, 1.0)));
+        let world = World { objects: vec![floor], lights: vec![light], ..World::default() };
+        let ray = Ray::new(Tuple::point(0.0, 1.0, 0.0), Tuple::vector(0.0, -1.0, 0.0));
+        assert_eq!(world.trace_path(&ray, 1, &mut Rng::new(3)), Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn indirect_light_brightens_a_closed_room() {
+        // Inside a uniformly emitting, white, perfectly diffuse sphere every path
+        // gathers the same geometric series, emitted / (1 - albedo).
+        let mut room = Shape::new(ShapeKind::Sphere);
+        room.material.color = Color::new(1.0, 1.0, 1.0);
+        room.material.diffuse = 0.5;
+        room.material.emissive = Color::new(1.0, 1.0, 1.0);
+        let world = World { objects: vec![room], ..World::default() };
+        let ray = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        let mut rng = Rng::new(11);
+        let paths = 4000;
+        let mean = (0..paths).map(|_| world.trace_path(&ray, 64, &mut rng).red).sum::<f64>() / paths as f64;
+        assert!((mean - 2.0).abs() < 0.05, "mean radiance {}", mean);
+        let stats = world.statistics();
+        assert!(stats.diffuse_rays > paths);
+        assert_eq!(stats.reflection_rays, 0);
+    }
+
+    #[test]
+    fn mirror_bounces_are_counted_apart_from_diffuse_ones() {
+        let mut room = Shape::new(ShapeKind::Sphere);
+        room.material.reflective = 1.0;
+        room.material.emissive = Color::new(1.0, 1.0, 1.0);
+        let world = World { objects: vec![room], ..World::default() };
+        let ray = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        let mut rng = Rng::new(3);
+        for _ in 0..200 {
+            world.trace_path(&ray, 8, &mut rng);
+        }
+        let stats = world.statistics();
+        assert!(stats.reflection_rays > 0 && stats.diffuse_rays > 0);
+        assert_eq!(stats.total_rays(), stats.reflection_rays + stats.diffuse_rays);
+    }
+}
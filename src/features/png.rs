@@ -0,0 +1,378 @@
+use std::io::{self, Write};
+
+use super::canvas::{quantize, Canvas};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+pub fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in bytes.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self { bytes: Vec::new(), buffer: 0, count: 0 }
+    }
+
+    // Deflate packs values least significant bit first.
+    fn write_bits(&mut self, value: u32, count: u32) {
+        self.buffer |= (value as u64) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // Huffman codes are the exception and go most significant bit first.
+    fn write_code(&mut self, code: u32, length: u32) {
+        self.write_bits(code.reverse_bits() >> (32 - length), length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+fn write_literal(bits: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => bits.write_code(0x30 + symbol, 8),
+        144..=255 => bits.write_code(0x190 + symbol - 144, 9),
+        256..=279 => bits.write_code(symbol - 256, 7),
+        _ => bits.write_code(0xc0 + symbol - 280, 8),
+    }
+}
+
+fn write_match(bits: &mut BitWriter, length: usize, distance: usize) {
+    let code = LENGTH_BASE.iter().rposition(|&base| base as usize <= length).unwrap();
+    write_literal(bits, 257 + code as u32);
+    bits.write_bits((length - LENGTH_BASE[code] as usize) as u32, LENGTH_EXTRA[code] as u32);
+    let code = DISTANCE_BASE.iter().rposition(|&base| base as usize <= distance).unwrap();
+    bits.write_code(code as u32, 5);
+    bits.write_bits((distance - DISTANCE_BASE[code] as usize) as u32, DISTANCE_EXTRA[code] as u32);
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let value = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+    (value.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+}
+
+// Compresses `data` into a zlib stream holding a single fixed-Huffman deflate block,
+// using greedy LZ77 matching against the most recent position with the same hash.
+pub fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    let mut bits = BitWriter::new();
+    bits.write_bits(1, 1);
+    bits.write_bits(1, 2);
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut position = 0;
+    while position < data.len() {
+        let (mut length, mut distance) = (0, 0);
+        if position + MIN_MATCH <= data.len() {
+            let slot = hash(&data[position..]);
+            let candidate = head[slot];
+            head[slot] = position;
+            if candidate != usize::MAX && position - candidate <= WINDOW_SIZE {
+                let limit = MAX_MATCH.min(data.len() - position);
+                while length < limit && data[candidate + length] == data[position + length] {
+                    length += 1;
+                }
+                distance = position - candidate;
+            }
+        }
+        if length >= MIN_MATCH {
+            write_match(&mut bits, length, distance);
+            for skipped in position + 1..position + length {
+                if skipped + MIN_MATCH <= data.len() {
+                    head[hash(&data[skipped..])] = skipped;
+                }
+            }
+            position += length;
+        } else {
+            write_literal(&mut bits, data[position] as u32);
+            position += 1;
+        }
+    }
+    write_literal(&mut bits, 256);
+
+    let mut stream = vec![0x78, 0x01];
+    stream.extend(bits.finish());
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let (to_left, to_up, to_up_left) = ((estimate - left as i16).abs(), (estimate - up as i16).abs(), (estimate - up_left as i16).abs());
+    if to_left <= to_up && to_left <= to_up_left {
+        left
+    } else if to_up <= to_up_left {
+        up
+    } else {
+        up_left
+    }
+}
+
+// Picks, per scanline, whichever PNG filter gives the smallest sum of absolute
+// differences, the usual heuristic for making rows compress well.
+fn filter_scanlines(raw: &[u8], stride: usize, bytes_per_pixel: usize) -> Vec<u8> {
+    let mut filtered = Vec::with_capacity(raw.len() + raw.len() / stride.max(1));
+    let zero_row = vec![0; stride];
+    let mut candidates = vec![vec![0u8; stride]; 5];
+    for (index, row) in raw.chunks(stride).enumerate() {
+        let previous = if index == 0 { &zero_row[..] } else { &raw[(index - 1) * stride..index * stride] };
+        for i in 0..stride {
+            let left = if i >= bytes_per_pixel { row[i - bytes_per_pixel] } else { 0 };
+            let up = previous[i];
+            let up_left = if i >= bytes_per_pixel { previous[i - bytes_per_pixel] } else { 0 };
+            candidates[0][i] = row[i];
+            candidates[1][i] = row[i].wrapping_sub(left);
+            candidates[2][i] = row[i].wrapping_sub(up);
+            candidates[3][i] = row[i].wrapping_sub(((left as u16 + up as u16) / 2) as u8);
+            candidates[4][i] = row[i].wrapping_sub(paeth(left, up, up_left));
+        }
+        let cost = |bytes: &Vec<u8>| bytes.iter().map(|&byte| (byte as i8).unsigned_abs() as u32).sum::<u32>();
+        let filter = (0..5).min_by_key(|&filter| cost(&candidates[filter])).unwrap();
+        filtered.push(filter as u8);
+        filtered.extend_from_slice(&candidates[filter]);
+    }
+    filtered
+}
+
+fn write_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    let mut crc_input = kind.to_vec();
+    crc_input.extend_from_slice(data);
+    writer.write_all(&crc_input)?;
+    writer.write_all(&crc32(&crc_input).to_be_bytes())
+}
+
+impl Canvas {
+    // Writes an 8-bit RGB PNG after applying the canvas's export pipeline.
+    // Canvases with an alpha plane are written as RGBA with straight (not
+    // premultiplied) colour, as PNG expects.
+    pub fn write_png<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        if self.width == 0 || self.height == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("cannot write a {}x{} canvas as PNG", self.width, self.height)));
+        }
+        let (bytes_per_pixel, color_type) = if self.alpha.is_some() { (4, 6) } else { (3, 2) };
+        let stride = self.width * bytes_per_pixel;
+        let mut raw = Vec::with_capacity(stride * self.height);
+        for (x, y, pixel) in self.enumerate_pixels() {
+            let rgba = self.rgba_at(x, y);
+            let pixel = self.export.apply(if self.alpha.is_some() { rgba.unpremultiplied() } else { pixel });
+            for channel in [pixel.red, pixel.green, pixel.blue] {
+                raw.push(quantize(channel, 255) as u8);
+            }
+            if self.alpha.is_some() {
+                raw.push(quantize(rgba.alpha, 255) as u8);
+            }
+        }
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        header.extend_from_slice(&[8, color_type, 0, 0, 0]);
+
+        writer.write_all(&SIGNATURE)?;
+        write_chunk(&mut writer, b"IHDR", &header)?;
+        write_chunk(&mut writer, b"IDAT", &zlib_compress(&filter_scanlines(&raw, stride, bytes_per_pixel)))?;
+        write_chunk(&mut writer, b"IEND", &[])?;
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::{color::Color, tone::ExportPipeline};
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+    }
+
+    #[test]
+    fn adler32_check_value() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        assert_eq!(adler32(&[]), 1);
+    }
+
+    #[test]
+    fn compressing_empty_input() {
+        assert_eq!(zlib_compress(&[]), vec![0x78, 0x01, 0x03, 0x00, 0x00, 0x00, 0x00, 0x01]);
+    }
+
+    #[test]
+    fn repeated_data_compresses() {
+        let data = vec![7u8; 10000];
+        assert!(zlib_compress(&data).len() < 200);
+    }
+
+    #[test]
+    fn paeth_predictor() {
+        assert_eq!(paeth(10, 20, 10), 20);
+        assert_eq!(paeth(20, 10, 10), 20);
+        assert_eq!(paeth(10, 10, 20), 10);
+    }
+
+    #[test]
+    fn writing_png_chunks() {
+        let mut c = Canvas::new(2, 1);
+        c.write_pixel(0, 0, Color::new(1.0, 0.0, 0.0));
+        let mut png = Vec::new();
+        c.write_png(&mut png).unwrap();
+        assert_eq!(png[..8], SIGNATURE);
+        assert_eq!(png[8..16], [0, 0, 0, 13, b'I', b'H', b'D', b'R']);
+        assert_eq!(png[16..29], [0, 0, 0, 2, 0, 0, 0, 1, 8, 2, 0, 0, 0]);
+        assert_eq!(png[png.len() - 12..], [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]);
+    }
+
+    // Just enough of a deflate decoder for the single fixed-Huffman block that
+    // zlib_compress writes.
+    fn inflate(stream: &[u8]) -> Vec<u8> {
+        let data = &stream[2..stream.len() - 4];
+        let mut position = 0;
+        let mut bit = |count: u32| {
+            let mut value = 0;
+            for i in 0..count {
+                value |= (((data[position / 8] >> (position % 8)) & 1) as u32) << i;
+                position += 1;
+            }
+            value
+        };
+        assert_eq!((bit(1), bit(2)), (1, 1));
+        let mut output: Vec<u8> = Vec::new();
+        loop {
+            let mut code = 0;
+            let mut length = 0;
+            let symbol = loop {
+                code = code << 1 | bit(1);
+                length += 1;
+                match (length, code) {
+                    (7, 0..=0x17) => break code + 256,
+                    (8, 0x30..=0xbf) => break code - 0x30,
+                    (8, 0xc0..=0xc7) => break code - 0xc0 + 280,
+                    (9, 0x190..=0x1ff) => break code - 0x190 + 144,
+                    _ => {}
+                }
+            };
+            match symbol {
+                0..=255 => output.push(symbol as u8),
+                256 => break,
+                _ => {
+                    let index = symbol as usize - 257;
+                    let length = LENGTH_BASE[index] as usize + bit(LENGTH_EXTRA[index] as u32) as usize;
+                    let index = (0..5).fold(0, |code, _| code << 1 | bit(1)) as usize;
+                    let distance = DISTANCE_BASE[index] as usize + bit(DISTANCE_EXTRA[index] as u32) as usize;
+                    for _ in 0..length {
+                        output.push(output[output.len() - distance]);
+                    }
+                }
+            }
+        }
+        assert_eq!(stream[stream.len() - 4..], adler32(&output).to_be_bytes());
+        output
+    }
+
+    fn unfilter(filtered: &[u8], stride: usize, bytes_per_pixel: usize) -> Vec<u8> {
+        let mut raw: Vec<u8> = Vec::new();
+        for (index, row) in filtered.chunks(stride + 1).enumerate() {
+            for i in 0..stride {
+                let left = if i >= bytes_per_pixel { raw[raw.len() - bytes_per_pixel] } else { 0 };
+                let up = if index > 0 { raw[raw.len() - stride] } else { 0 };
+                let up_left = if index > 0 && i >= bytes_per_pixel { raw[raw.len() - stride - bytes_per_pixel] } else { 0 };
+                let predicted = match row[0] {
+                    0 => 0,
+                    1 => left,
+                    2 => up,
+                    3 => ((left as u16 + up as u16) / 2) as u8,
+                    _ => paeth(left, up, up_left),
+                };
+                raw.push(row[i + 1].wrapping_add(predicted));
+            }
+        }
+        raw
+    }
+
+    #[test]
+    fn pixels_round_trip_through_the_compressed_stream() {
+        let mut c = Canvas::new(19, 7);
+        c.export = ExportPipeline::linear();
+        let mut expected = Vec::new();
+        for y in 0..7 {
+            for x in 0..19 {
+                let bytes = [(x * 13) as u8, (y * 37) as u8, ((x * y) % 5 * 50) as u8];
+                c.write_pixel(x, y, Color::new(bytes[0] as f64 / 255.0, bytes[1] as f64 / 255.0, bytes[2] as f64 / 255.0));
+                expected.extend_from_slice(&bytes);
+            }
+        }
+        let mut png = Vec::new();
+        c.write_png(&mut png).unwrap();
+        let length = u32::from_be_bytes(png[33..37].try_into().unwrap()) as usize;
+        assert_eq!(png[37..41], *b"IDAT");
+        let filtered = inflate(&png[41..41 + length]);
+        assert_eq!(filtered.len(), 7 * (19 * 3 + 1));
+        assert_eq!(unfilter(&filtered, 19 * 3, 3), expected);
+    }
+
+    #[test]
+    fn empty_canvases_cannot_be_written() {
+        for c in [Canvas::new(0, 3), Canvas::new(3, 0)] {
+            assert_eq!(c.write_png(&mut Vec::new()).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn canvases_with_alpha_are_written_as_rgba() {
+        let mut c = Canvas::new(2, 1);
+        c.write_alpha(1, 0, 0.0);
+        let mut png = Vec::new();
+        c.write_png(&mut png).unwrap();
+        assert_eq!(png[16..29], [0, 0, 0, 2, 0, 0, 0, 1, 8, 6, 0, 0, 0]);
+    }
+}
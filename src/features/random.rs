@@ -0,0 +1,62 @@
+// Small xorshift64* generator so sampling stays dependency-free and reproducible:
+// the same seed always yields the same sequence, whichever thread draws from it.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // Run the seed through splitmix64 so nearby seeds (e.g. neighbouring pixels)
+        // start from unrelated states, and never from the all-zero state.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        Self { state: if z == 0 { 1 } else { z } }
+    }
+
+    pub fn for_pixel(x: usize, y: usize) -> Self {
+        Self::new(((y as u64) << 32) ^ x as u64)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_gives_same_sequence() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        for _ in 0..10 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+    }
+
+    #[test]
+    fn floats_are_in_unit_interval() {
+        let mut rng = Rng::new(0);
+        for _ in 0..1000 {
+            let value = rng.next_f64();
+            assert!((0.0..1.0).contains(&value));
+        }
+    }
+
+    #[test]
+    fn neighbouring_pixels_get_different_sequences() {
+        assert_ne!(Rng::for_pixel(0, 0).next_u64(), Rng::for_pixel(1, 0).next_u64());
+        assert_ne!(Rng::for_pixel(0, 0).next_u64(), Rng::for_pixel(0, 1).next_u64());
+    }
+}
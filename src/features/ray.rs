@@ -0,0 +1,43 @@
+use super::{matrix::Matrix, tuple::Tuple};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Tuple,
+    pub direction: Tuple,
+}
+
+impl Ray {
+    pub fn new(origin: Tuple, direction: Tuple) -> Self {
+        Self { origin, direction }
+    }
+
+    pub fn position(&self, t: f64) -> Tuple {
+        self.origin + self.direction * t
+    }
+
+    pub fn transform(&self, matrix: &Matrix) -> Self {
+        Self::new(matrix * self.origin, matrix * self.direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computing_a_point_from_a_distance() {
+        let ray = Ray::new(Tuple::point(2.0, 3.0, 4.0), Tuple::vector(1.0, 0.0, 0.0));
+        assert_eq!(ray.position(0.0), Tuple::point(2.0, 3.0, 4.0));
+        assert_eq!(ray.position(-1.0), Tuple::point(1.0, 3.0, 4.0));
+        assert_eq!(ray.position(2.5), Tuple::point(4.5, 3.0, 4.0));
+    }
+
+    #[test]
+    fn transforming_a_ray() {
+        let ray = Ray::new(Tuple::point(1.0, 2.0, 3.0), Tuple::vector(0.0, 1.0, 0.0));
+        let translated = ray.transform(&Matrix::translation(3.0, 4.0, 5.0));
+        assert_eq!(translated, Ray::new(Tuple::point(4.0, 6.0, 8.0), Tuple::vector(0.0, 1.0, 0.0)));
+        let scaled = ray.transform(&Matrix::scaling(2.0, 3.0, 4.0));
+        assert_eq!(scaled, Ray::new(Tuple::point(2.0, 6.0, 12.0), Tuple::vector(0.0, 3.0, 0.0)));
+    }
+}
@@ -0,0 +1,322 @@
+use std::{
+    slice::ChunksMut,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{
+    canvas::Canvas,
+    color::{Color, Rgba},
+    sampling::{Sample, Sampler},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    pub completed_rows: usize,
+    pub total_rows: usize,
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    pub fn is_complete(&self) -> bool {
+        self.completed_rows == self.total_rows
+    }
+}
+
+pub struct Renderer {
+    pub threads: usize,
+}
+
+impl Renderer {
+    pub fn new(threads: usize) -> Self {
+        Self { threads }
+    }
+
+    pub fn thread_count(&self) -> usize {
+        if self.threads > 0 {
+            self.threads
+        } else {
+            thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+        }
+    }
+
+    pub fn render<F>(&self, width: usize, height: usize, shade: F) -> Canvas
+    where
+        F: Fn(usize, usize) -> Color + Sync,
+    {
+        self.render_with_progress(width, height, shade, |_| {}, &AtomicBool::new(false))
+    }
+
+    pub fn render_sampled<F>(&self, width: usize, height: usize, sampler: &Sampler, shade: F) -> Canvas
+    where
+        F: Fn(f64, f64) -> Color + Sync,
+    {
+        self.render_sampled_with_progress(width, height, sampler, shade, |_| {}, &AtomicBool::new(false))
+    }
+
+    // Samplers that share pixel corners render in two passes, the corner lattice and
+    // then the pixels, and progress counts the rows of both.
+    pub fn render_sampled_with_progress<F, P>(&self, width: usize, height: usize, sampler: &Sampler, shade: F, progress: P, cancel: &AtomicBool) -> Canvas
+    where
+        F: Fn(f64, f64) -> Color + Sync,
+        P: Fn(Progress) + Sync,
+    {
+        let mut canvas = Canvas::new(width, height);
+        canvas.pixels = self.sample_pixels(width, height, sampler, &shade, &progress, cancel);
+        canvas
+    }
+
+    // Rows are handed out one at a time, so every pixel is computed exactly once and
+    // written straight into its own row of the canvas regardless of which thread ran it.
+    // The cancel flag is checked before each row is taken; rows that were never started
+    // are left black in the returned canvas.
+    pub fn render_with_progress<F, P>(&self, width: usize, height: usize, shade: F, progress: P, cancel: &AtomicBool) -> Canvas
+    where
+        F: Fn(usize, usize) -> Color + Sync,
+        P: Fn(Progress) + Sync,
+    {
+        let mut canvas = Canvas::new(width, height);
+        self.fill_rows(canvas.rows_mut(), height, shade, progress, cancel);
+        canvas
+    }
+
+    // Renders with coverage: `shade` returns None where a sample hits nothing. The
+    // canvas gets an alpha plane with the fraction of samples that hit, and colours
+    // premultiplied by it, ready to be composited with Canvas::over.
+    pub fn render_with_coverage<F>(&self, width: usize, height: usize, sampler: &Sampler, shade: F) -> Canvas
+    where
+        F: Fn(f64, f64) -> Option<Color> + Sync,
+    {
+        let shade = |x: f64, y: f64| shade(x, y).map_or(Rgba::transparent(), Rgba::opaque);
+        let samples = self.sample_pixels(width, height, sampler, &shade, &|_| {}, &AtomicBool::new(false));
+
+        let mut canvas = Canvas::new(width, height);
+        canvas.pixels = samples.iter().map(|sample| sample.color).collect();
+        canvas.alpha = Some(samples.iter().map(|sample| sample.alpha).collect());
+        canvas
+    }
+
+    // Samplers that share pixel corners get the whole corner lattice shaded up front,
+    // so each corner is traced once rather than by all four pixels that touch it.
+    fn sample_pixels<S, F, P>(&self, width: usize, height: usize, sampler: &Sampler, shade: &F, progress: &P, cancel: &AtomicBool) -> Vec<S>
+    where
+        S: Sample + Send + Sync,
+        F: Fn(f64, f64) -> S + Sync,
+        P: Fn(Progress) + Sync,
+    {
+        let mut samples = vec![S::zero(); width * height];
+        if !sampler.shares_corners() || width == 0 || height == 0 {
+            self.fill_rows(samples.chunks_mut(width.max(1)), height, |x, y| sampler.sample(x, y, shade), progress, cancel);
+            return samples;
+        }
+
+        let total_rows = 2 * height + 1;
+        let start = Instant::now();
+        let pass = |offset: usize| move |p: Progress| progress(Progress { completed_rows: offset + p.completed_rows, total_rows, elapsed: start.elapsed() });
+        let stride = width + 1;
+        let mut corners = vec![S::zero(); stride * (height + 1)];
+        self.fill_rows(corners.chunks_mut(stride), height + 1, |x, y| shade(x as f64, y as f64), pass(0), cancel);
+        let corner = |x: usize, y: usize| corners[y * stride + x];
+        self.fill_rows(
+            samples.chunks_mut(width),
+            height,
+            |x, y| sampler.sample_from_corners(x, y, [corner(x, y), corner(x + 1, y), corner(x, y + 1), corner(x + 1, y + 1)], shade),
+            pass(height + 1),
+            cancel,
+        );
+        samples
+    }
+
+    fn fill_rows<T, F, P>(&self, rows: ChunksMut<'_, T>, height: usize, shade: F, progress: P, cancel: &AtomicBool)
+    where
+        T: Send,
+        F: Fn(usize, usize) -> T + Sync,
+        P: Fn(Progress) + Sync,
+    {
+        let rows = Mutex::new(rows.enumerate());
+        let workers = self.thread_count().min(height.max(1));
+        let completed = AtomicUsize::new(0);
+        let start = Instant::now();
+
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    if cancel.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let next = rows.lock().unwrap().next();
+                    let Some((y, row)) = next else {
+                        break;
+                    };
+                    for (x, pixel) in row.iter_mut().enumerate() {
+                        *pixel = shade(x, y);
+                    }
+                    progress(Progress {
+                        completed_rows: completed.fetch_add(1, Ordering::Relaxed) + 1,
+                        total_rows: height,
+                        elapsed: start.elapsed(),
+                    });
+                });
+            }
+        });
+    }
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::{camera::Camera, matrix::Matrix, sampling::Strategy, shape::Shape, tuple::Tuple, world::World};
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    fn gradient(x: usize, y: usize) -> Color {
+        Color::new(x as f64 / 10.0, y as f64 / 10.0, 0.5)
+    }
+
+    #[test]
+    fn scene_types_are_send_and_sync() {
+        assert_send_sync::<Tuple>();
+        assert_send_sync::<Color>();
+        assert_send_sync::<Matrix>();
+        assert_send_sync::<Canvas>();
+        assert_send_sync::<Renderer>();
+        assert_send_sync::<Shape>();
+        assert_send_sync::<World>();
+        assert_send_sync::<Camera>();
+    }
+
+    #[test]
+    fn rendering_writes_every_pixel() {
+        let canvas = Renderer::new(4).render(7, 5, gradient);
+        assert_eq!(canvas.width, 7);
+        assert_eq!(canvas.height, 5);
+        for y in 0..5 {
+            for x in 0..7 {
+                assert_eq!(canvas.pixel_at(x, y), gradient(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_render_matches_single_threaded_render() {
+        let single = Renderer::new(1).render(16, 9, gradient);
+        let parallel = Renderer::new(8).render(16, 9, gradient);
+        assert_eq!(single.pixels, parallel.pixels);
+    }
+
+    #[test]
+    fn sampled_render_anti_aliases_edges() {
+        let sampler = Sampler::new(Strategy::Grid, 4);
+        let edge = |x: f64, _y: f64| if x < 1.5 { Color::new(1.0, 1.0, 1.0) } else { Color::new(0.0, 0.0, 0.0) };
+        let canvas = Renderer::new(2).render_sampled(3, 1, &sampler, edge);
+        assert_eq!(canvas.pixel_at(0, 0), Color::new(1.0, 1.0, 1.0));
+        assert_eq!(canvas.pixel_at(1, 0), Color::new(0.5, 0.5, 0.5));
+        assert_eq!(canvas.pixel_at(2, 0), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn adaptive_render_shades_each_corner_once() {
+        let calls = AtomicUsize::new(0);
+        let flat = |_x: f64, _y: f64| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            Color::new(0.3, 0.3, 0.3)
+        };
+        let sampler = Sampler::adaptive(Color::new(0.1, 0.1, 0.1), 3);
+        let canvas = Renderer::new(3).render_sampled(20, 10, &sampler, flat);
+        assert_eq!(canvas.pixel_at(19, 9), Color::new(0.3, 0.3, 0.3));
+        assert_eq!(calls.load(Ordering::Relaxed), 21 * 11 + 20 * 10);
+    }
+
+    #[test]
+    fn adaptive_render_matches_per_pixel_sampling() {
+        let sampler = Sampler::adaptive(Color::new(0.1, 0.1, 0.1), 2);
+        let disc = |x: f64, y: f64| if (x - 4.0).powi(2) + (y - 3.0).powi(2) < 6.0 { Color::new(1.0, 0.5, 0.0) } else { Color::new(0.0, 0.0, 0.0) };
+        let canvas = Renderer::new(2).render_sampled(8, 6, &sampler, disc);
+        for y in 0..6 {
+            for x in 0..8 {
+                assert_eq!(canvas.pixel_at(x, y), sampler.sample(x, y, &disc));
+            }
+        }
+    }
+
+    #[test]
+    fn progress_is_reported_for_every_row() {
+        let reports = Mutex::new(Vec::new());
+        Renderer::new(3).render_with_progress(4, 6, gradient, |p| reports.lock().unwrap().push(p), &AtomicBool::new(false));
+        let mut reports = reports.into_inner().unwrap();
+        reports.sort_by_key(|p| p.completed_rows);
+        assert_eq!(reports.len(), 6);
+        assert!(reports.iter().all(|p| p.total_rows == 6));
+        assert!(reports.last().unwrap().is_complete());
+    }
+
+    #[test]
+    fn cancelling_returns_partial_canvas() {
+        let cancel = AtomicBool::new(false);
+        let canvas = Renderer::new(1).render_with_progress(
+            3,
+            5,
+            |_, _| Color::new(1.0, 1.0, 1.0),
+            |p| {
+                if p.completed_rows == 2 {
+                    cancel.store(true, Ordering::Relaxed);
+                }
+            },
+            &cancel,
+        );
+        assert_eq!(canvas.pixel_at(0, 1), Color::new(1.0, 1.0, 1.0));
+        assert_eq!(canvas.pixel_at(0, 2), Color::new(0.0, 0.0, 0.0));
+        assert_eq!(canvas.pixel_at(2, 4), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn adaptive_progress_counts_both_passes() {
+        let reports = Mutex::new(Vec::new());
+        let sampler = Sampler::adaptive(Color::new(0.1, 0.1, 0.1), 2);
+        let flat = |_x: f64, _y: f64| Color::new(0.3, 0.3, 0.3);
+        Renderer::new(3).render_sampled_with_progress(5, 4, &sampler, flat, |p| reports.lock().unwrap().push(p), &AtomicBool::new(false));
+        let mut reports = reports.into_inner().unwrap();
+        reports.sort_by_key(|p| p.completed_rows);
+        assert_eq!(reports.len(), 9);
+        assert!(reports.iter().all(|p| p.total_rows == 9));
+        assert!(reports.last().unwrap().is_complete());
+    }
+
+    #[test]
+    fn cancelling_an_adaptive_render_stops_both_passes() {
+        let calls = AtomicUsize::new(0);
+        let flat = |_x: f64, _y: f64| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            Color::new(0.3, 0.3, 0.3)
+        };
+        let sampler = Sampler::adaptive(Color::new(0.1, 0.1, 0.1), 2);
+        let canvas = Renderer::new(2).render_sampled_with_progress(4, 3, &sampler, flat, |_| {}, &AtomicBool::new(true));
+        assert_eq!(calls.load(Ordering::Relaxed), 0);
+        assert_eq!(canvas.pixel_at(3, 2), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn coverage_records_partial_hits_at_edges() {
+        let sampler = Sampler::new(Strategy::Grid, 4);
+        let half_plane = |x: f64, _y: f64| if x < 1.5 { Some(Color::new(1.0, 0.5, 0.0)) } else { None };
+        let canvas = Renderer::new(2).render_with_coverage(3, 2, &sampler, half_plane);
+        assert_eq!(canvas.rgba_at(0, 1), Rgba::opaque(Color::new(1.0, 0.5, 0.0)));
+        assert_eq!(canvas.rgba_at(1, 0), Rgba::new(Color::new(0.5, 0.25, 0.0), 0.5));
+        assert_eq!(canvas.rgba_at(2, 1), Rgba::transparent());
+    }
+
+    #[test]
+    fn default_renderer_uses_available_cores() {
+        assert!(Renderer::default().thread_count() >= 1);
+    }
+}
@@ -0,0 +1,280 @@
+use std::ops::{Add, Mul};
+
+use super::{
+    color::{Color, Rgba},
+    random::Rng,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    Grid,
+    Jittered,
+    Random,
+    Adaptive { threshold: Color, max_depth: usize },
+}
+
+// The corners and centre an adaptive sample starts from.
+const ADAPTIVE_OFFSETS: [(f64, f64); 5] = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0), (0.5, 0.5)];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampler {
+    pub strategy: Strategy,
+    pub samples: usize,
+}
+
+impl Sampler {
+    pub fn new(strategy: Strategy, samples: usize) -> Self {
+        Self { strategy, samples }
+    }
+
+    pub fn adaptive(threshold: Color, max_depth: usize) -> Self {
+        Self::new(Strategy::Adaptive { threshold, max_depth }, ADAPTIVE_OFFSETS.len())
+    }
+
+    // Grid and jittered sampling split the pixel into an n x n lattice, so their
+    // sample count is rounded to the nearest square. Adaptive sampling reports the
+    // five samples every pixel is averaged from, its corners and centre; it only adds
+    // more where the pixel needs them. Neighbouring pixels share corners, so a render
+    // traces about two rays per pixel for those five.
+    pub fn samples_per_pixel(&self) -> usize {
+        match self.strategy {
+            Strategy::Random => self.samples.max(1),
+            Strategy::Adaptive { .. } => ADAPTIVE_OFFSETS.len(),
+            Strategy::Grid | Strategy::Jittered => {
+                let n = self.grid_size();
+                n * n
+            }
+        }
+    }
+
+    fn grid_size(&self) -> usize {
+        ((self.samples as f64).sqrt().round() as usize).max(1)
+    }
+
+    // Offsets within the pixel, each component in [0, 1].
+    pub fn offsets(&self, rng: &mut Rng) -> Vec<(f64, f64)> {
+        match self.strategy {
+            Strategy::Adaptive { .. } => ADAPTIVE_OFFSETS.to_vec(),
+            Strategy::Random => (0..self.samples_per_pixel()).map(|_| (rng.next_f64(), rng.next_f64())).collect(),
+            Strategy::Grid | Strategy::Jittered => {
+                let n = self.grid_size();
+                let cell = 1.0 / n as f64;
+                let mut offsets = Vec::with_capacity(n * n);
+                for j in 0..n {
+                    for i in 0..n {
+                        let (du, dv) = match self.strategy {
+                            Strategy::Jittered => (rng.next_f64(), rng.next_f64()),
+                            _ => (0.5, 0.5),
+                        };
+                        offsets.push(((i as f64 + du) * cell, (j as f64 + dv) * cell));
+                    }
+                }
+                offsets
+            }
+        }
+    }
+
+    // Shades the pixel at (x, y) by averaging `shade` over the sample positions, which
+    // are given in canvas space (the pixel's centre is at x + 0.5, y + 0.5).
+    pub fn sample<S, F>(&self, x: usize, y: usize, shade: &F) -> S
+    where
+        S: Sample,
+        F: Fn(f64, f64) -> S,
+    {
+        if self.shares_corners() {
+            let (cx, cy) = (x as f64, y as f64);
+            let corners = [shade(cx, cy), shade(cx + 1.0, cy), shade(cx, cy + 1.0), shade(cx + 1.0, cy + 1.0)];
+            return self.sample_from_corners(x, y, corners, shade);
+        }
+        let mut rng = Rng::for_pixel(x, y);
+        let offsets = self.offsets(&mut rng);
+        let mut sum = S::zero();
+        for (du, dv) in offsets.iter() {
+            sum = sum + shade(x as f64 + du, y as f64 + dv);
+        }
+        sum * (1.0 / offsets.len() as f64)
+    }
+
+    // Adaptive sampling starts from the pixel's corners, which neighbouring pixels
+    // share. A renderer can shade the (width + 1) x (height + 1) corner lattice once
+    // and hand each pixel its corners, top left, top right, bottom left, bottom right.
+    pub fn shares_corners(&self) -> bool {
+        matches!(self.strategy, Strategy::Adaptive { .. })
+    }
+
+    pub fn sample_from_corners<S, F>(&self, x: usize, y: usize, corners: [S; 4], shade: &F) -> S
+    where
+        S: Sample,
+        F: Fn(f64, f64) -> S,
+    {
+        match self.strategy {
+            Strategy::Adaptive { threshold, max_depth } => {
+                Refiner { threshold, max_depth, shade }.refine(x as f64, y as f64, 1.0, corners, 0)
+            }
+            _ => self.sample(x, y, shade),
+        }
+    }
+}
+
+// Anything a sampler can average: plain colours, or colours with coverage.
+pub trait Sample: Copy + Add<Output = Self> + Mul<f64, Output = Self> {
+    fn zero() -> Self;
+    fn differs(&self, other: &Self, threshold: Color) -> bool;
+}
+
+impl Sample for Color {
+    fn zero() -> Self {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    fn differs(&self, other: &Self, threshold: Color) -> bool {
+        (self.red - other.red).abs() > threshold.red
+            || (self.green - other.green).abs() > threshold.green
+            || (self.blue - other.blue).abs() > threshold.blue
+    }
+}
+
+// Coverage edges are refined like colour edges, against the smallest channel threshold.
+impl Sample for Rgba {
+    fn zero() -> Self {
+        Rgba::transparent()
+    }
+
+    fn differs(&self, other: &Self, threshold: Color) -> bool {
+        let alpha_threshold = threshold.red.min(threshold.green).min(threshold.blue);
+        self.color.differs(&other.color, threshold) || (self.alpha - other.alpha).abs() > alpha_threshold
+    }
+}
+
+struct Refiner<'a, F> {
+    threshold: Color,
+    max_depth: usize,
+    shade: &'a F,
+}
+
+impl<S, F> Refiner<'_, F>
+where
+    S: Sample,
+    F: Fn(f64, f64) -> S,
+{
+    // Samples the centre of the square at (x, y) with the given side length. If any corner
+    // differs from the centre by more than the threshold, the square is split into four
+    // quadrants that are refined in turn, reusing the samples already taken.
+    fn refine(&self, x: f64, y: f64, size: f64, corners: [S; 4], depth: usize) -> S {
+        let shade = self.shade;
+        let half = size / 2.0;
+        let centre = shade(x + half, y + half);
+        let [top_left, top_right, bottom_left, bottom_right] = corners;
+        if depth >= self.max_depth || !corners.iter().any(|corner| corner.differs(&centre, self.threshold)) {
+            return (top_left + top_right + bottom_left + bottom_right + centre) * 0.2;
+        }
+
+        let top = shade(x + half, y);
+        let left = shade(x, y + half);
+        let right = shade(x + size, y + half);
+        let bottom = shade(x + half, y + size);
+        let quadrants = [
+            self.refine(x, y, half, [top_left, top, left, centre], depth + 1),
+            self.refine(x + half, y, half, [top, top_right, centre, right], depth + 1),
+            self.refine(x, y + half, half, [left, centre, bottom_left, bottom], depth + 1),
+            self.refine(x + half, y + half, half, [centre, right, bottom, bottom_right], depth + 1),
+        ];
+        (quadrants[0] + quadrants[1] + quadrants[2] + quadrants[3]) * 0.25
+    }
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Self::new(Strategy::Grid, 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn left_half_white(x: f64, _y: f64) -> Color {
+        if x < 0.5 {
+            Color::new(1.0, 1.0, 1.0)
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        }
+    }
+
+    #[test]
+    fn default_sampler_shoots_through_pixel_centre() {
+        let sampler = Sampler::default();
+        let offsets = sampler.offsets(&mut Rng::new(0));
+        assert_eq!(offsets, vec![(0.5, 0.5)]);
+    }
+
+    #[test]
+    fn grid_sampling_rounds_to_square() {
+        assert_eq!(Sampler::new(Strategy::Grid, 4).samples_per_pixel(), 4);
+        assert_eq!(Sampler::new(Strategy::Jittered, 10).samples_per_pixel(), 9);
+        assert_eq!(Sampler::new(Strategy::Random, 10).samples_per_pixel(), 10);
+        assert_eq!(Sampler::new(Strategy::Grid, 0).samples_per_pixel(), 1);
+        let adaptive = Sampler::adaptive(Color::new(0.1, 0.1, 0.1), 2);
+        assert_eq!(adaptive.samples_per_pixel(), adaptive.offsets(&mut Rng::new(0)).len());
+        assert_eq!(adaptive.samples_per_pixel(), adaptive.samples);
+    }
+
+    #[test]
+    fn jittered_samples_stay_in_their_cells() {
+        let sampler = Sampler::new(Strategy::Jittered, 4);
+        let offsets = sampler.offsets(&mut Rng::new(7));
+        let cells = [(0.0, 0.0), (0.5, 0.0), (0.0, 0.5), (0.5, 0.5)];
+        for ((u, v), (cu, cv)) in offsets.iter().zip(cells.iter()) {
+            assert!(*u >= *cu && *u < cu + 0.5);
+            assert!(*v >= *cv && *v < cv + 0.5);
+        }
+    }
+
+    #[test]
+    fn supersampling_averages_across_an_edge() {
+        let sampler = Sampler::new(Strategy::Grid, 4);
+        assert_eq!(sampler.sample(0, 0, &left_half_white), Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn adaptive_sampling_takes_five_samples_in_flat_regions() {
+        let calls = std::cell::Cell::new(0);
+        let flat = |_x: f64, _y: f64| {
+            calls.set(calls.get() + 1);
+            Color::new(0.3, 0.3, 0.3)
+        };
+        let sampler = Sampler::adaptive(Color::new(0.1, 0.1, 0.1), 3);
+        assert_eq!(sampler.sample(4, 4, &flat), Color::new(0.3, 0.3, 0.3));
+        assert_eq!(calls.get(), 5);
+    }
+
+    #[test]
+    fn adaptive_sampling_refines_edges_up_to_max_depth() {
+        let calls = std::cell::Cell::new(0);
+        let edge = |x: f64, y: f64| {
+            calls.set(calls.get() + 1);
+            left_half_white(x, y)
+        };
+        let shallow = Sampler::adaptive(Color::new(0.1, 0.1, 0.1), 0);
+        shallow.sample(0, 0, &edge);
+        let shallow_calls = calls.replace(0);
+        let deep = Sampler::adaptive(Color::new(0.1, 0.1, 0.1), 3);
+        let color = deep.sample(0, 0, &edge);
+        assert_eq!(shallow_calls, 5);
+        assert!(calls.get() > 5);
+        assert!((color.red - 0.5).abs() < 0.1);
+    }
+
+    #[test]
+    fn sampling_coverage_counts_hits() {
+        let sampler = Sampler::new(Strategy::Grid, 4);
+        let disc = |x: f64, _y: f64| if x < 0.5 { Rgba::opaque(Color::new(1.0, 0.0, 0.0)) } else { Rgba::transparent() };
+        assert_eq!(sampler.sample(0, 0, &disc), Rgba::new(Color::new(0.5, 0.0, 0.0), 0.5));
+    }
+
+    #[test]
+    fn sampling_is_deterministic_per_pixel() {
+        let sampler = Sampler::new(Strategy::Random, 16);
+        assert_eq!(sampler.sample::<Color, _>(3, 2, &left_half_white), sampler.sample(3, 2, &left_half_white));
+    }
+}
@@ -0,0 +1,1023 @@
+use std::{collections::HashMap, f64::consts::PI, fmt, fs, path::Path, sync::Arc};
+
+use super::{
+    bump::Bump,
+    camera::Camera,
+    canvas::{Canvas, ImageError},
+    color::Color,
+    error,
+    light::{AreaLight, DirectionalLight, Light, PointLight, SpotLight},
+    material::{Brdf, Material},
+    microfacet::Microfacet,
+    matrix::Matrix,
+    shape::{Shape, ShapeKind},
+    texture::{ImageTexture, TextureFilter, UvMapping},
+    tone::srgb_decode,
+    tuple::Tuple,
+    util::EPSILON,
+    world::World,
+    yaml::{self, Node, Value},
+};
+
+// Scene files are a list of `add` and `define` entries:
+//
+//   - add: camera
+//     width: 100
+//     height: 50
+//     field-of-view: 1.047
+//     from: [0, 1.5, -5]
+//     to: [0, 1, 0]
+//     up: [0, 1, 0]
+//   - add: light
+//     at: [-10, 10, -10]
+//     intensity: [1, 1, 1]
+//   - add: spot-light
+//     at: [0, 5, 0]
+//     direction: [0, -1, 0]
+//     cone-angle: 0.5
+//     falloff: 0.1
+//     intensity: [1, 1, 1]
+//   - add: directional-light
+//     direction: [-1, -2, 1]
+//     intensity: [0.8, 0.8, 0.7]
+//   - add: area-light
+//     corner: [-1, 2, 4]
+//     uvec: [2, 0, 0]
+//     usteps: 4
+//     vvec: [0, 2, 0]
+//     vsteps: 4
+//     jitter: true
+//     intensity: [1.5, 1.5, 1.5]
+//   - define: shiny
+//     value: { specular: 1, shininess: 300 }
+//   - define: red-shiny
+//     extend: shiny
+//     value: { color: [1, 0, 0] }
+//   - add: sphere
+//     material:
+//       texture: { file: earth.ppm, mapping: spherical, filter: bilinear }
+//       bump: { normal-map: { file: earth-normals.ppm, mapping: spherical } }
+//   - add: sphere
+//     material: red-shiny
+//     transform:
+//       - [scale, 0.5, 0.5, 0.5]
+//       - [translate, 0, 1, 0]
+//
+// Transform steps apply in the order they are listed, and a name in a transform
+// list splices in a defined list of steps. Rotations are in radians. Texture files
+// are PPM images, found relative to the scene file; colour textures are read as
+// sRGB, height and normal maps as raw values. A bump is one of `noise: <scale>`,
+// `height-map: <texture>` or `normal-map: <texture>`, with a `strength` for the
+// first two.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SceneError {
+    pub location: Location,
+    pub message: String,
+}
+
+impl SceneError {
+    pub fn new(location: Location, message: impl Into<String>) -> Self {
+        Self { location, message: message.into() }
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+// A problem found while checking a scene. Unlike SceneError, which stops loading,
+// every diagnostic in a scene is reported together.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub location: Location,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error(location: Location, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Error, location, message: message.into() }
+    }
+
+    pub fn warning(location: Location, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Warning, location, message: message.into() }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}: {}: {}", self.location, severity, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraDescription {
+    pub width: usize,
+    pub height: usize,
+    pub field_of_view: f64,
+    pub from: Tuple,
+    pub to: Tuple,
+    pub up: Tuple,
+    pub location: Location,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LightDescription {
+    pub light: Light,
+    pub location: Location,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialDescription {
+    pub color: Color,
+    pub ambient: f64,
+    pub diffuse: f64,
+    pub specular: f64,
+    pub shininess: f64,
+    pub reflective: f64,
+    pub transparency: f64,
+    pub refractive_index: f64,
+    pub emissive: Color,
+    // Set once either `roughness` or `metallic` is given.
+    pub microfacet: Option<Microfacet>,
+    pub texture: Option<TextureDescription>,
+    pub bump: Option<BumpDescription>,
+    pub location: Location,
+}
+
+impl Default for MaterialDescription {
+    fn default() -> Self {
+        Self {
+            color: Color::new(1.0, 1.0, 1.0),
+            ambient: 0.1,
+            diffuse: 0.9,
+            specular: 0.9,
+            shininess: 200.0,
+            reflective: 0.0,
+            transparency: 0.0,
+            refractive_index: 1.0,
+            emissive: Color::new(0.0, 0.0, 0.0),
+            microfacet: None,
+            texture: None,
+            bump: None,
+            location: Location::default(),
+        }
+    }
+}
+
+impl MaterialDescription {
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let color = self.color;
+        let coefficients = [
+            ("red", color.red),
+            ("green", color.green),
+            ("blue", color.blue),
+            ("ambient", self.ambient),
+            ("diffuse", self.diffuse),
+            ("specular", self.specular),
+            ("shininess", self.shininess),
+            ("reflective", self.reflective),
+            ("transparency", self.transparency),
+            ("emissive red", self.emissive.red),
+            ("emissive green", self.emissive.green),
+            ("emissive blue", self.emissive.blue),
+        ];
+        let mut diagnostics: Vec<Diagnostic> = coefficients
+            .iter()
+            .filter(|(_, value)| *value < 0.0)
+            .map(|(name, value)| Diagnostic::error(self.location, format!("material {} is negative ({})", name, value)))
+            .collect();
+        if self.refractive_index <= 0.0 {
+            diagnostics.push(Diagnostic::error(self.location, "material refractive index must be positive"));
+        }
+        for texture in self.textures() {
+            if !texture.transform.is_invertible() {
+                diagnostics.push(Diagnostic::error(texture.location, "the texture's transform is not invertible"));
+            }
+            if !Path::new(&texture.file).is_file() {
+                diagnostics.push(Diagnostic::error(texture.location, format!("cannot find the texture file '{}'", texture.file)));
+            }
+        }
+        if let Some(BumpDescription { source: BumpSource::Noise { scale }, location, .. }) = self.bump {
+            if scale <= 0.0 {
+                diagnostics.push(Diagnostic::error(location, "the noise scale must be positive"));
+            }
+        }
+        if let Some(surface) = self.microfacet {
+            for (name, value) in [("roughness", surface.roughness), ("metallic", surface.metallic)] {
+                if !(0.0..=1.0).contains(&value) {
+                    diagnostics.push(Diagnostic::error(self.location, format!("material {} must be between 0 and 1 ({})", name, value)));
+                }
+            }
+        }
+        diagnostics
+    }
+
+    // The colour texture and any image the bump reads.
+    pub fn textures(&self) -> impl Iterator<Item = &TextureDescription> {
+        let bump = self.bump.as_ref().and_then(|bump| match &bump.source {
+            BumpSource::HeightMap(texture) | BumpSource::NormalMap(texture) => Some(texture),
+            BumpSource::Noise { .. } => None,
+        });
+        self.texture.iter().chain(bump)
+    }
+
+    fn textures_mut(&mut self) -> impl Iterator<Item = &mut TextureDescription> {
+        let bump = self.bump.as_mut().and_then(|bump| match &mut bump.source {
+            BumpSource::HeightMap(texture) | BumpSource::NormalMap(texture) => Some(texture),
+            BumpSource::Noise { .. } => None,
+        });
+        self.texture.iter_mut().chain(bump)
+    }
+
+    // The material without its texture or bump, which may need images loaded first.
+    pub fn material(&self) -> Material {
+        Material {
+            color: self.color,
+            ambient: self.ambient,
+            diffuse: self.diffuse,
+            specular: self.specular,
+            shininess: self.shininess,
+            reflective: self.reflective,
+            transparency: self.transparency,
+            refractive_index: self.refractive_index,
+            emissive: self.emissive,
+            brdf: self.microfacet.map_or(Brdf::Phong, Brdf::Microfacet),
+            texture: None,
+            bump: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureDescription {
+    pub file: String,
+    pub mapping: UvMapping,
+    pub filter: TextureFilter,
+    pub transform: Matrix,
+    pub location: Location,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BumpSource {
+    Noise { scale: f64 },
+    HeightMap(TextureDescription),
+    NormalMap(TextureDescription),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BumpDescription {
+    pub source: BumpSource,
+    pub strength: f64,
+    pub location: Location,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapeDescription {
+    pub kind: ShapeKind,
+    pub transform: Matrix,
+    pub material: MaterialDescription,
+    pub location: Location,
+    pub transform_location: Location,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneDescription {
+    pub camera: CameraDescription,
+    pub lights: Vec<LightDescription>,
+    pub shapes: Vec<ShapeDescription>,
+    // Problems found while loading that did not stop it, such as unknown references.
+    pub diagnostics: Vec<Diagnostic>,
+    // Where the list of entries starts, for problems with the scene as a whole.
+    pub location: Location,
+}
+
+impl SceneDescription {
+    // Fails only on malformed input. An unknown reference is recorded in
+    // `diagnostics` and loading carries on without it, so that `validate` can report
+    // it together with everything else.
+    pub fn parse(source: &str) -> Result<Self, SceneError> {
+        let document = yaml::parse(source)?;
+        let Value::Sequence(entries) = &document.value else {
+            return Err(SceneError::new(document.location, "a scene must be a list of entries"));
+        };
+
+        let mut loader = Loader { defines: HashMap::new(), diagnostics: Vec::new(), expanding: Vec::new() };
+        let mut camera = None;
+        let mut lights = Vec::new();
+        let mut shapes = Vec::new();
+        for entry in entries {
+            if let Some(name) = entry.get("define") {
+                loader.define(entry, name)?;
+                continue;
+            }
+            let Some(kind) = entry.get("add") else {
+                return Err(SceneError::new(entry.location, "expected an 'add' or 'define' entry"));
+            };
+            match text(kind)? {
+                "camera" if camera.is_some() => return Err(SceneError::new(kind.location, "the scene already has a camera")),
+                "camera" => camera = Some(loader.camera(entry)?),
+                "light" => lights.push(loader.light(entry)?),
+                "spot-light" => lights.push(loader.spot_light(entry)?),
+                "directional-light" => lights.push(loader.directional_light(entry)?),
+                "area-light" => lights.push(loader.area_light(entry)?),
+                "sphere" => shapes.push(loader.shape(entry, ShapeKind::Sphere)?),
+                "plane" => shapes.push(loader.shape(entry, ShapeKind::Plane)?),
+                "cube" => shapes.push(loader.shape(entry, ShapeKind::Cube)?),
+                other => return Err(SceneError::new(kind.location, format!("unknown kind of object '{}'", other))),
+            }
+        }
+
+        let camera = camera.ok_or_else(|| SceneError::new(document.location, "the scene has no camera"))?;
+        Ok(Self { camera, lights, shapes, diagnostics: loader.diagnostics, location: document.location })
+    }
+
+    pub fn from_file(filename: &str) -> error::Result<Self> {
+        let mut scene = Self::parse(&fs::read_to_string(filename)?)?;
+        let directory = Path::new(filename).parent().unwrap_or(Path::new(""));
+        for texture in scene.shapes.iter_mut().flat_map(|shape| shape.material.textures_mut()) {
+            texture.file = directory.join(&texture.file).to_string_lossy().into_owned();
+        }
+        Ok(scene)
+    }
+
+    // Everything that would make the scene fail to build or render wrongly, sorted by
+    // location.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = self.diagnostics.clone();
+        let camera = &self.camera;
+        let forward = camera.to - camera.from;
+        if camera.up.magnitude() < EPSILON {
+            diagnostics.push(Diagnostic::error(camera.location, "the camera's up vector has zero length"));
+        } else if forward.magnitude() < EPSILON {
+            diagnostics.push(Diagnostic::error(camera.location, "the camera looks from and to the same point"));
+        } else if forward.normalize().cross(&camera.up.normalize()).magnitude() < EPSILON {
+            diagnostics.push(Diagnostic::error(camera.location, "the camera's up vector is parallel to its view direction"));
+        }
+        if !(camera.field_of_view > 0.0 && camera.field_of_view < PI) {
+            diagnostics.push(Diagnostic::error(camera.location, "the field of view must be between 0 and pi radians"));
+        }
+
+        // Emissive materials light the scene when path tracing, so only a scene with
+        // neither lights nor emitters is certain to render black.
+        let emits = self.shapes.iter().any(|shape| shape.material.emissive != Color::new(0.0, 0.0, 0.0));
+        if self.lights.is_empty() && !emits {
+            diagnostics.push(Diagnostic::warning(self.location, "the scene has no lights, so it will render black"));
+        }
+        for light in &self.lights {
+            let intensity = light.light.intensity();
+            if intensity.red < 0.0 || intensity.green < 0.0 || intensity.blue < 0.0 {
+                diagnostics.push(Diagnostic::error(light.location, "light intensity must not be negative"));
+            }
+            match light.light {
+                Light::Spot(spot) => {
+                    if spot.direction.magnitude() < EPSILON {
+                        diagnostics.push(Diagnostic::error(light.location, "the spot light's direction has zero length"));
+                    }
+                    if !(spot.cone_angle > 0.0 && spot.cone_angle < PI) {
+                        diagnostics.push(Diagnostic::error(light.location, "the cone angle must be between 0 and pi radians"));
+                    }
+                    if !(spot.falloff >= 0.0 && spot.falloff <= spot.cone_angle) {
+                        diagnostics.push(Diagnostic::error(light.location, "the falloff must be between 0 and the cone angle"));
+                    }
+                }
+                Light::Directional(sun) if sun.direction.magnitude() < EPSILON => {
+                    diagnostics.push(Diagnostic::error(light.location, "the directional light's direction has zero length"));
+                }
+                Light::Area(area) if area.uvec.cross(&area.vvec).magnitude() < EPSILON => {
+                    diagnostics.push(Diagnostic::error(light.location, "the area light's edges must span a rectangle"));
+                }
+                _ => {}
+            }
+        }
+
+        for shape in &self.shapes {
+            if !shape.transform.is_invertible() {
+                diagnostics.push(Diagnostic::error(shape.transform_location, format!("the {}'s transform is not invertible", shape.kind.name())));
+            }
+            diagnostics.extend(shape.material.validate());
+        }
+
+        diagnostics.sort_by(|a, b| (a.location, &a.message).cmp(&(b.location, &b.message)));
+        diagnostics.dedup();
+        diagnostics
+    }
+
+    pub fn camera(&self) -> error::Result<Camera> {
+        let description = &self.camera;
+        let mut camera = Camera::new(description.width, description.height, description.field_of_view);
+        camera.set_transform(Matrix::view_transform(description.from, description.to, description.up))?;
+        Ok(camera)
+    }
+
+    // Each texture file is read once, however many materials use it.
+    pub fn world(&self) -> error::Result<World> {
+        let mut world = World::new();
+        world.lights = self.lights.iter().map(|light| light.light).collect();
+        // Colour textures are sRGB encoded, like the images the renderer writes, and are
+        // decoded to linear light. Height and normal maps hold data and are used as stored.
+        let mut images: HashMap<(String, bool), Arc<Canvas>> = HashMap::new();
+        let mut load = |texture: &TextureDescription, color: bool| -> error::Result<ImageTexture> {
+            let key = (texture.file.clone(), color);
+            let image = match images.get(&key) {
+                Some(image) => image.clone(),
+                None => {
+                    let mut image = Canvas::ppm_from_file(&texture.file)?;
+                    if image.width == 0 || image.height == 0 {
+                        let message = format!("texture '{}' is {}x{}, with no pixels to sample", texture.file, image.width, image.height);
+                        return Err(ImageError::Malformed(message).into());
+                    }
+                    if color {
+                        for pixel in image.pixels.iter_mut() {
+                            *pixel = Color::new(srgb_decode(pixel.red), srgb_decode(pixel.green), srgb_decode(pixel.blue));
+                        }
+                    }
+                    let image = Arc::new(image);
+                    images.insert(key, image.clone());
+                    image
+                }
+            };
+            let mut loaded = ImageTexture::new(image, texture.mapping, texture.filter);
+            loaded.set_transform(texture.transform.clone())?;
+            Ok(loaded)
+        };
+        for description in &self.shapes {
+            let mut shape = Shape::new(description.kind);
+            let material = &description.material;
+            shape.material = material.material();
+            shape.material.texture = material.texture.as_ref().map(|texture| load(texture, true)).transpose()?;
+            shape.material.bump = match &material.bump {
+                Some(bump) => Some(match &bump.source {
+                    BumpSource::Noise { scale } => Bump::Noise { scale: *scale, strength: bump.strength },
+                    BumpSource::HeightMap(texture) => Bump::HeightMap { texture: load(texture, false)?, strength: bump.strength },
+                    BumpSource::NormalMap(texture) => Bump::NormalMap { texture: load(texture, false)? },
+                }),
+                None => None,
+            };
+            shape.set_transform(description.transform.clone())?;
+            world.objects.push(shape);
+        }
+        Ok(world)
+    }
+}
+
+fn text(node: &Node) -> Result<&str, SceneError> {
+    node.as_str().ok_or_else(|| SceneError::new(node.location, "expected a name"))
+}
+
+fn number(node: &Node) -> Result<f64, SceneError> {
+    node.as_f64().ok_or_else(|| SceneError::new(node.location, "expected a number"))
+}
+
+fn triple(node: &Node) -> Result<[f64; 3], SceneError> {
+    match &node.value {
+        Value::Sequence(items) if items.len() == 3 => Ok([number(&items[0])?, number(&items[1])?, number(&items[2])?]),
+        _ => Err(SceneError::new(node.location, "expected a list of three numbers")),
+    }
+}
+
+fn entries(node: &Node) -> Result<&[(String, Node)], SceneError> {
+    match &node.value {
+        Value::Mapping(entries) => Ok(entries),
+        _ => Err(SceneError::new(node.location, "expected a mapping")),
+    }
+}
+
+fn required<'a>(entry: &'a Node, key: &str) -> Result<&'a Node, SceneError> {
+    entry.get(key).ok_or_else(|| SceneError::new(entry.location, format!("missing '{}'", key)))
+}
+
+fn count(entry: &Node, key: &str) -> Result<usize, SceneError> {
+    let node = required(entry, key)?;
+    match number(node)? {
+        value if value >= 1.0 && value.fract() == 0.0 => Ok(value as usize),
+        _ => Err(SceneError::new(node.location, format!("'{}' must be a positive whole number", key))),
+    }
+}
+
+fn point(entry: &Node, key: &str) -> Result<Tuple, SceneError> {
+    let [x, y, z] = triple(required(entry, key)?)?;
+    Ok(Tuple::point(x, y, z))
+}
+
+fn vector(entry: &Node, key: &str) -> Result<Tuple, SceneError> {
+    let [x, y, z] = triple(required(entry, key)?)?;
+    Ok(Tuple::vector(x, y, z))
+}
+
+fn color(entry: &Node, key: &str) -> Result<Color, SceneError> {
+    let [red, green, blue] = triple(required(entry, key)?)?;
+    Ok(Color::new(red, green, blue))
+}
+
+fn check_keys(entry: &Node, allowed: &[&str]) -> Result<(), SceneError> {
+    for (key, value) in entries(entry)? {
+        if !allowed.contains(&key.as_str()) {
+            return Err(SceneError::new(value.location, format!("unknown key '{}'", key)));
+        }
+    }
+    Ok(())
+}
+
+struct Loader {
+    defines: HashMap<String, Node>,
+    diagnostics: Vec<Diagnostic>,
+    // Names of the transform lists being spliced in, to catch cycles.
+    expanding: Vec<String>,
+}
+
+impl Loader {
+    fn define(&mut self, entry: &Node, name: &Node) -> Result<(), SceneError> {
+        check_keys(entry, &["define", "extend", "value"])?;
+        let mut value = required(entry, "value")?.clone();
+        if let Some(base) = entry.get("extend") {
+            if let Some(parent) = self.resolve(base)? {
+                value.value = match (parent.value, value.value) {
+                    (Value::Mapping(inherited), Value::Mapping(overrides)) => {
+                        let mut merged: Vec<(String, Node)> =
+                            inherited.into_iter().filter(|(key, _)| !overrides.iter().any(|(other, _)| other == key)).collect();
+                        merged.extend(overrides);
+                        Value::Mapping(merged)
+                    }
+                    (Value::Sequence(inherited), Value::Sequence(steps)) => Value::Sequence(inherited.into_iter().chain(steps).collect()),
+                    _ => return Err(SceneError::new(base.location, "can only extend a definition of the same shape")),
+                };
+            }
+        }
+        self.defines.insert(text(name)?.to_string(), value);
+        Ok(())
+    }
+
+    // Looks up a defined name, recording a diagnostic if there is no such definition.
+    fn resolve(&mut self, name: &Node) -> Result<Option<Node>, SceneError> {
+        let key = text(name)?;
+        let defined = self.defines.get(key).cloned();
+        if defined.is_none() {
+            self.diagnostics.push(Diagnostic::error(name.location, format!("unknown reference '{}'", key)));
+        }
+        Ok(defined)
+    }
+
+    fn camera(&self, entry: &Node) -> Result<CameraDescription, SceneError> {
+        check_keys(entry, &["add", "width", "height", "field-of-view", "from", "to", "up"])?;
+        Ok(CameraDescription {
+            width: count(entry, "width")?,
+            height: count(entry, "height")?,
+            field_of_view: number(required(entry, "field-of-view")?)?,
+            from: point(entry, "from")?,
+            to: point(entry, "to")?,
+            up: vector(entry, "up")?,
+            location: entry.location,
+        })
+    }
+
+    fn light(&self, entry: &Node) -> Result<LightDescription, SceneError> {
+        check_keys(entry, &["add", "at", "intensity"])?;
+        let light = PointLight::new(point(entry, "at")?, color(entry, "intensity")?);
+        Ok(LightDescription { light: Light::Point(light), location: entry.location })
+    }
+
+    fn spot_light(&self, entry: &Node) -> Result<LightDescription, SceneError> {
+        check_keys(entry, &["add", "at", "direction", "cone-angle", "falloff", "intensity"])?;
+        let falloff = entry.get("falloff").map(number).transpose()?.unwrap_or(0.0);
+        let cone_angle = number(required(entry, "cone-angle")?)?;
+        let light = SpotLight::new(point(entry, "at")?, vector(entry, "direction")?, cone_angle, falloff, color(entry, "intensity")?);
+        Ok(LightDescription { light: Light::Spot(light), location: entry.location })
+    }
+
+    fn directional_light(&self, entry: &Node) -> Result<LightDescription, SceneError> {
+        check_keys(entry, &["add", "direction", "intensity"])?;
+        let light = DirectionalLight::new(vector(entry, "direction")?, color(entry, "intensity")?);
+        Ok(LightDescription { light: Light::Directional(light), location: entry.location })
+    }
+
+    fn area_light(&self, entry: &Node) -> Result<LightDescription, SceneError> {
+        check_keys(entry, &["add", "corner", "uvec", "usteps", "vvec", "vsteps", "jitter", "intensity"])?;
+        let (usteps, vsteps) = (count(entry, "usteps")?, count(entry, "vsteps")?);
+        let mut light = AreaLight::new(point(entry, "corner")?, vector(entry, "uvec")?, usteps, vector(entry, "vvec")?, vsteps, color(entry, "intensity")?);
+        if let Some(node) = entry.get("jitter") {
+            light.jitter = node.as_bool().ok_or_else(|| SceneError::new(node.location, "expected true or false"))?;
+        }
+        Ok(LightDescription { light: Light::Area(light), location: entry.location })
+    }
+
+    fn shape(&mut self, entry: &Node, kind: ShapeKind) -> Result<ShapeDescription, SceneError> {
+        check_keys(entry, &["add", "material", "transform"])?;
+        let material = match entry.get("material") {
+            Some(node) => self.material(node)?,
+            None => MaterialDescription { location: entry.location, ..MaterialDescription::default() },
+        };
+        let (transform, transform_location) = match entry.get("transform") {
+            Some(node) => (self.transform(node)?, node.location),
+            None => (Matrix::identity(4), entry.location),
+        };
+        Ok(ShapeDescription { kind, transform, material, location: entry.location, transform_location })
+    }
+
+    fn material(&mut self, node: &Node) -> Result<MaterialDescription, SceneError> {
+        if node.as_str().is_some() {
+            return match self.resolve(node)? {
+                Some(defined) if defined.as_str().is_none() => self.material(&defined),
+                Some(_) => Err(SceneError::new(node.location, "a material must be defined as a mapping")),
+                None => Ok(MaterialDescription { location: node.location, ..MaterialDescription::default() }),
+            };
+        }
+        let mut material = MaterialDescription { location: node.location, ..MaterialDescription::default() };
+        for (key, value) in entries(node)? {
+            match key.as_str() {
+                "color" => {
+                    let [red, green, blue] = triple(value)?;
+                    material.color = Color::new(red, green, blue);
+                }
+                "ambient" => material.ambient = number(value)?,
+                "diffuse" => material.diffuse = number(value)?,
+                "specular" => material.specular = number(value)?,
+                "shininess" => material.shininess = number(value)?,
+                "reflective" => material.reflective = number(value)?,
+                "transparency" => material.transparency = number(value)?,
+                "refractive-index" => material.refractive_index = number(value)?,
+                "emissive" => {
+                    let [red, green, blue] = triple(value)?;
+                    material.emissive = Color::new(red, green, blue);
+                }
+                "texture" => material.texture = Some(self.texture(value)?),
+                "bump" => material.bump = Some(self.bump(value)?),
+                "roughness" => material.microfacet.get_or_insert(Microfacet::new(0.5, 0.0)).roughness = number(value)?,
+                "metallic" => material.microfacet.get_or_insert(Microfacet::new(0.5, 0.0)).metallic = number(value)?,
+                _ => return Err(SceneError::new(value.location, format!("unknown material property '{}'", key))),
+            }
+        }
+        Ok(material)
+    }
+
+    fn bump(&mut self, node: &Node) -> Result<BumpDescription, SceneError> {
+        check_keys(node, &["noise", "height-map", "normal-map", "strength"])?;
+        let sources: Vec<&(String, Node)> = entries(node)?.iter().filter(|(key, _)| key != "strength").collect();
+        let [(kind, value)] = sources[..] else {
+            return Err(SceneError::new(node.location, "a bump needs exactly one of 'noise', 'height-map' or 'normal-map'"));
+        };
+        let source = match kind.as_str() {
+            "noise" => BumpSource::Noise { scale: number(value)? },
+            "height-map" => BumpSource::HeightMap(self.texture(value)?),
+            _ => BumpSource::NormalMap(self.texture(value)?),
+        };
+        let strength = match node.get("strength") {
+            Some(strength) if kind != "normal-map" => number(strength)?,
+            Some(strength) => return Err(SceneError::new(strength.location, "a normal map has no strength")),
+            None => 1.0,
+        };
+        Ok(BumpDescription { source, strength, location: node.location })
+    }
+
+    fn texture(&mut self, node: &Node) -> Result<TextureDescription, SceneError> {
+        check_keys(node, &["file", "mapping", "filter", "transform"])?;
+        let file = required(node, "file")?;
+        let file = file.as_str().ok_or_else(|| SceneError::new(file.location, "expected a file name"))?.to_string();
+        let mapping_node = required(node, "mapping")?;
+        let name = text(mapping_node)?;
+        let mapping = UvMapping::from_name(name).ok_or_else(|| SceneError::new(mapping_node.location, format!("unknown texture mapping '{}'", name)))?;
+        let filter = match node.get("filter") {
+            Some(filter_node) => {
+                let name = text(filter_node)?;
+                TextureFilter::from_name(name).ok_or_else(|| SceneError::new(filter_node.location, format!("unknown texture filter '{}'", name)))?
+            }
+            None => TextureFilter::Bilinear,
+        };
+        Ok(TextureDescription {
+            file,
+            mapping,
+            filter,
+            transform: match node.get("transform") {
+                Some(steps) => self.transform(steps)?,
+                None => Matrix::identity(4),
+            },
+            location: node.location,
+        })
+    }
+
+    fn transform(&mut self, node: &Node) -> Result<Matrix, SceneError> {
+        let Value::Sequence(steps) = &node.value else {
+            return Err(SceneError::new(node.location, "expected a list of transform steps"));
+        };
+        let mut transform = Matrix::identity(4);
+        for step in steps {
+            let matrix = match &step.value {
+                Value::String(name) => {
+                    if self.expanding.contains(name) {
+                        return Err(SceneError::new(step.location, format!("'{}' refers to itself", name)));
+                    }
+                    match self.resolve(step)? {
+                        Some(defined) => {
+                            self.expanding.push(name.clone());
+                            let expanded = self.transform(&defined);
+                            self.expanding.pop();
+                            expanded?
+                        }
+                        None => Matrix::identity(4),
+                    }
+                }
+                Value::Sequence(items) if !items.is_empty() => transform_step(text(&items[0])?, &items[1..], step.location)?,
+                _ => return Err(SceneError::new(step.location, "expected a transform step or a defined name")),
+            };
+            transform = matrix * transform;
+        }
+        Ok(transform)
+    }
+}
+
+fn transform_step(name: &str, arguments: &[Node], location: Location) -> Result<Matrix, SceneError> {
+    let arguments = arguments.iter().map(number).collect::<Result<Vec<f64>, SceneError>>()?;
+    let expected = match name {
+        "translate" | "scale" => 3,
+        "rotate-x" | "rotate_x" | "rotate-y" | "rotate_y" | "rotate-z" | "rotate_z" => 1,
+        "shear" => 6,
+        _ => return Err(SceneError::new(location, format!("unknown transform '{}'", name))),
+    };
+    if arguments.len() != expected {
+        return Err(SceneError::new(location, format!("'{}' takes {} numbers, got {}", name, expected, arguments.len())));
+    }
+    let a = &arguments;
+    Ok(match name {
+        "translate" => Matrix::translation(a[0], a[1], a[2]),
+        "scale" => Matrix::scaling(a[0], a[1], a[2]),
+        "rotate-x" | "rotate_x" => Matrix::rotation_x(a[0]),
+        "rotate-y" | "rotate_y" => Matrix::rotation_y(a[0]),
+        "rotate-z" | "rotate_z" => Matrix::rotation_z(a[0]),
+        _ => Matrix::shearing(a[0], a[1], a[2], a[3], a[4], a[5]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    const CAMERA: &str = "\
+- add: camera
+  width: 100
+  height: 50
+  field-of-view: 0.785
+  from: [0, 1.5, -5]
+  to: [0, 1, 0]
+  up: [0, 1, 0]
+";
+
+    #[test]
+    fn loading_the_example_scene() {
+        let scene = SceneDescription::parse(include_str!("../../scenes/spheres.yml")).unwrap();
+        assert_eq!((scene.camera.width, scene.camera.height), (200, 100));
+        assert_eq!(scene.camera.up, Tuple::vector(0.0, 1.0, 0.0));
+        assert_eq!(scene.lights.len(), 1);
+        assert_eq!(scene.shapes.len(), 4);
+        assert_eq!(scene.shapes[0].kind, ShapeKind::Plane);
+
+        let world = scene.world().unwrap();
+        assert_eq!(world.objects.len(), 4);
+        assert_eq!(world.objects[1].material.color, Color::new(0.1, 1.0, 0.5));
+        assert_eq!(scene.camera().unwrap().width, 200);
+    }
+
+    #[test]
+    fn loading_cameras_and_lights() {
+        let source = format!("{}- add: light\n  at: [-10, 10, -10]\n  intensity: [1, 0.5, 1]\n", CAMERA);
+        let scene = SceneDescription::parse(&source).unwrap();
+        assert_eq!(scene.camera.field_of_view, 0.785);
+        assert_eq!(scene.camera.from, Tuple::point(0.0, 1.5, -5.0));
+        assert_eq!(scene.lights[0].light, Light::Point(PointLight::new(Tuple::point(-10.0, 10.0, -10.0), Color::new(1.0, 0.5, 1.0))));
+        assert_eq!(scene.lights[0].location, Location { line: 8, column: 3 });
+    }
+
+    #[test]
+    fn loading_spot_and_directional_lights() {
+        let spot = "- add: spot-light\n  at: [0, 5, 0]\n  direction: [0, -1, 0]\n  cone-angle: 0.5\n  intensity: [1, 1, 1]\n";
+        let sun = "- add: directional-light\n  direction: [0, -1, 0]\n  intensity: [1, 1, 1]\n";
+        let scene = SceneDescription::parse(&format!("{}{}{}", CAMERA, spot, sun)).unwrap();
+        let Light::Spot(light) = scene.lights[0].light else { panic!("expected a spot light") };
+        assert_eq!((light.cone_angle, light.falloff), (0.5, 0.0));
+        assert_eq!(scene.lights[1].light, Light::Directional(DirectionalLight::new(Tuple::vector(0.0, -1.0, 0.0), Color::new(1.0, 1.0, 1.0))));
+        assert!(scene.validate().is_empty());
+
+        let wide = format!("{}{}", CAMERA, spot.replace("cone-angle: 0.5", "cone-angle: 0.5\n  falloff: 0.7"));
+        let diagnostics = SceneDescription::parse(&wide).unwrap().validate();
+        assert_eq!(diagnostics[0].message, "the falloff must be between 0 and the cone angle");
+    }
+
+    #[test]
+    fn loading_area_lights() {
+        let light = "- add: area-light\n  corner: [-1, 2, 4]\n  uvec: [2, 0, 0]\n  usteps: 4\n  vvec: [0, 2, 0]\n  vsteps: 2\n  jitter: false\n  intensity: [1, 1, 1]\n";
+        let scene = SceneDescription::parse(&format!("{}{}", CAMERA, light)).unwrap();
+        let Light::Area(area) = scene.lights[0].light else { panic!("expected an area light") };
+        assert_eq!((area.usteps, area.vsteps, area.jitter), (4, 2, false));
+        assert_eq!(area.uvec, Tuple::vector(0.5, 0.0, 0.0));
+        assert_eq!(area.vvec, Tuple::vector(0.0, 1.0, 0.0));
+
+        let flat = SceneDescription::parse(&format!("{}{}", CAMERA, light.replace("[0, 2, 0]", "[4, 0, 0]"))).unwrap();
+        assert_eq!(flat.validate()[0].message, "the area light's edges must span a rectangle");
+        let error = SceneDescription::parse(&format!("{}{}", CAMERA, light.replace("usteps: 4", "usteps: 0"))).unwrap_err();
+        assert_eq!(error.message, "'usteps' must be a positive whole number");
+    }
+
+    #[test]
+    fn transform_steps_apply_in_order() {
+        let source = format!(
+            "{}- define: lift\n  value:\n    - [translate, 0, 1, 0]\n- add: sphere\n  transform:\n    - [rotate-z, {}]\n    - [scale, 2, 2, 2]\n    - lift\n",
+            CAMERA,
+            PI / 2.0
+        );
+        let scene = SceneDescription::parse(&source).unwrap();
+        let expected = Matrix::translation(0.0, 1.0, 0.0) * Matrix::scaling(2.0, 2.0, 2.0) * Matrix::rotation_z(PI / 2.0);
+        assert_eq!(scene.shapes[0].transform, expected);
+        assert_eq!(scene.shapes[0].transform.clone() * Tuple::point(1.0, 0.0, 0.0), Tuple::point(0.0, 3.0, 0.0));
+    }
+
+    #[test]
+    fn materials_extend_definitions() {
+        let source = format!(
+            "{}- define: base\n  value: {{ diffuse: 0.5, color: [1, 1, 1] }}\n- define: red\n  extend: base\n  value: {{ color: [1, 0, 0] }}\n- add: cube\n  material: red\n- add: plane\n  material: {{ ambient: 1 }}\n",
+            CAMERA
+        );
+        let scene = SceneDescription::parse(&source).unwrap();
+        let red = &scene.shapes[0].material;
+        assert_eq!(red.color, Color::new(1.0, 0.0, 0.0));
+        assert_eq!(red.diffuse, 0.5);
+        assert_eq!(red.specular, 0.9);
+        assert_eq!(scene.shapes[1].material.ambient, 1.0);
+        assert_eq!(scene.shapes[1].material.diffuse, 0.9);
+    }
+
+    #[test]
+    fn textures_load_relative_to_the_scene_file() {
+        let directory = std::env::temp_dir().join(format!("ray-tracer-challenge-{}-textures", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("stripes.ppm"), "P3\n2 1\n255\n255 0 0 0 0 188\n").unwrap();
+        let texture = "  material:\n    texture: { file: stripes.ppm, mapping: planar, filter: nearest }\n";
+        let bumped = format!("{}    bump: {{ normal-map: {{ file: stripes.ppm, mapping: cube }} }}\n", texture);
+        let scene_path = directory.join("scene.yml");
+        fs::write(&scene_path, format!("{}- add: plane\n{}- add: cube\n{}", CAMERA, texture, bumped)).unwrap();
+
+        let scene = SceneDescription::from_file(scene_path.to_str().unwrap()).unwrap();
+        assert_eq!(scene.validate().len(), 1);
+        let world = scene.world().unwrap();
+        let plane = &world.objects[0];
+        assert_eq!(plane.material.color_at(plane, Tuple::point(0.2, 0.0, 0.5)), Color::new(1.0, 0.0, 0.0));
+        assert!((plane.material.color_at(plane, Tuple::point(0.8, 0.0, 0.5)).blue - 0.5).abs() < 0.005);
+        assert_eq!(plane.material.texture, world.objects[1].material.texture);
+        let Some(Bump::NormalMap { texture: normal_map }) = &world.objects[1].material.bump else { panic!("expected a normal map") };
+        assert_eq!(normal_map.image.pixel_at(1, 0), Color::new(0.0, 0.0, 188.0 / 255.0));
+        assert_eq!(normal_map.mapping, UvMapping::Cube);
+        fs::write(directory.join("stripes.ppm"), "P3\n0 0\n255\n").unwrap();
+        assert!(matches!(scene.world(), Err(error::Error::Image(ImageError::Malformed(_)))));
+        fs::remove_dir_all(&directory).unwrap();
+
+        let diagnostics = SceneDescription::parse(&format!("{}- add: plane\n{}", CAMERA, texture)).unwrap().validate();
+        assert_eq!(diagnostics[1].message, "cannot find the texture file 'stripes.ppm'");
+        let error = SceneDescription::parse(&format!("{}- add: plane\n{}", CAMERA, texture.replace("planar", "conical"))).unwrap_err();
+        assert_eq!(error.message, "unknown texture mapping 'conical'");
+    }
+
+    #[test]
+    fn loading_bumps() {
+        let bump = |value: &str| SceneDescription::parse(&format!("{}- add: sphere\n  material:\n    bump: {}\n", CAMERA, value));
+        let scene = bump("{ noise: 4, strength: 0.3 }").unwrap();
+        assert_eq!(scene.world().unwrap().objects[0].material.bump, Some(Bump::Noise { scale: 4.0, strength: 0.3 }));
+        let Some(BumpDescription { source: BumpSource::HeightMap(texture), strength, .. }) = bump("{ height-map: { file: bricks.ppm, mapping: spherical } }").unwrap().shapes[0].material.bump.clone() else {
+            panic!("expected a height map")
+        };
+        assert_eq!((texture.file.as_str(), strength), ("bricks.ppm", 1.0));
+
+        assert_eq!(bump("{ noise: 0 }").unwrap().validate()[1].message, "the noise scale must be positive");
+        assert_eq!(bump("{ strength: 2 }").unwrap_err().message, "a bump needs exactly one of 'noise', 'height-map' or 'normal-map'");
+        assert_eq!(bump("{ normal-map: { file: n.ppm, mapping: planar }, strength: 2 }").unwrap_err().message, "a normal map has no strength");
+    }
+
+    #[test]
+    fn roughness_or_metallic_makes_a_microfacet_material() {
+        let source = format!("{}- add: sphere\n  material: {{ metallic: 1, color: [1, 0.8, 0.3] }}\n- add: plane\n  material: {{ roughness: 1.5 }}\n", CAMERA);
+        let scene = SceneDescription::parse(&source).unwrap();
+        assert_eq!(scene.shapes[0].material.material().brdf, Brdf::Microfacet(Microfacet::new(0.5, 1.0)));
+        let diagnostics = scene.validate();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[1].message, "material roughness must be between 0 and 1 (1.5)");
+    }
+
+    #[test]
+    fn scenes_in_json() {
+        let source = r#"[
+            {"add": "camera", "width": 10, "height": 10, "field-of-view": 1, "from": [0, 0, -5], "to": [0, 0, 0], "up": [0, 1, 0]},
+            {"add": "sphere", "transform": [["shear", 1, 0, 0, 0, 0, 0]]}
+        ]"#;
+        let scene = SceneDescription::parse(source).unwrap();
+        assert_eq!(scene.shapes[0].transform, Matrix::shearing(1.0, 0.0, 0.0, 0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn errors_carry_locations() {
+        let error = SceneDescription::parse(&format!("{}- add: sphere\n  transform: [[scale, 1, 2]]\n", CAMERA)).unwrap_err();
+        assert_eq!(error.location, Location { line: 9, column: 15 });
+
+        let error = SceneDescription::parse("- add: light\n  at: [0, 0, 0]\n  intensity: [1, 1, 1]\n").unwrap_err();
+        assert_eq!(error.message, "the scene has no camera");
+        assert!(SceneDescription::parse(&format!("{}- add: teapot\n", CAMERA)).is_err());
+    }
+
+    #[test]
+    fn validation_reports_every_problem() {
+        let source = "\
+- add: camera
+  width: 10
+  height: 10
+  field-of-view: 1
+  from: [0, 0, -5]
+  to: [0, 0, 0]
+  up: [0, 0, 0]
+- define: bad
+  value: { diffuse: -0.5, shininess: -1 }
+- add: sphere
+  material: bad
+  transform: [[scale, 0, 1, 1]]
+- add: cube
+  material: missing
+  transform: [unknown, [translate, 1, 2, 3]]
+- add: plane
+  material: bad
+";
+        let scene = SceneDescription::parse(source).unwrap();
+        let diagnostics = scene.validate();
+        let summary: Vec<(usize, usize, Severity)> =
+            diagnostics.iter().map(|diagnostic| (diagnostic.location.line, diagnostic.location.column, diagnostic.severity)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1, 1, Severity::Warning),
+                (1, 3, Severity::Error),
+                (9, 10, Severity::Error),
+                (9, 10, Severity::Error),
+                (12, 14, Severity::Error),
+                (14, 13, Severity::Error),
+                (15, 15, Severity::Error),
+            ]
+        );
+        assert_eq!(diagnostics[0].to_string(), "line 1, column 1: warning: the scene has no lights, so it will render black");
+        assert_eq!(diagnostics[1].message, "the camera's up vector has zero length");
+        assert_eq!(diagnostics[4].message, "the sphere's transform is not invertible");
+        assert_eq!(diagnostics[5].message, "unknown reference 'missing'");
+        assert_eq!(scene.shapes[1].transform, Matrix::translation(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn valid_scenes_have_no_diagnostics() {
+        let scene = SceneDescription::parse(include_str!("../../scenes/spheres.yml")).unwrap();
+        assert!(scene.validate().is_empty());
+    }
+
+    #[test]
+    fn missing_lights_are_reported_where_the_scene_starts() {
+        let scene = SceneDescription::parse(&format!("# no lights\n\n{}", CAMERA)).unwrap();
+        let diagnostics = scene.validate();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].location, Location { line: 3, column: 1 });
+
+        let glowing = format!("{}- add: sphere\n  material: {{ emissive: [1, 1, 1] }}\n", CAMERA);
+        assert!(SceneDescription::parse(&glowing).unwrap().validate().is_empty());
+    }
+
+    #[test]
+    fn cameras_looking_along_their_up_vector_are_rejected() {
+        let source = CAMERA.replace("up: [0, 1, 0]", "up: [0, -1, 10]");
+        let messages: Vec<String> = SceneDescription::parse(&source).unwrap().validate().into_iter().map(|diagnostic| diagnostic.message).collect();
+        assert!(messages.contains(&"the camera's up vector is parallel to its view direction".to_string()));
+    }
+
+    #[test]
+    fn self_referencing_transforms_are_errors() {
+        let source = format!("{}- define: loop\n  value: [loop]\n- add: sphere\n  transform: [loop]\n", CAMERA);
+        assert_eq!(SceneDescription::parse(&source).unwrap_err().message, "'loop' refers to itself");
+    }
+}
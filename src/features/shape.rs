@@ -0,0 +1,202 @@
+use super::{error::Result, material::Material, matrix::Matrix, ray::Ray, tuple::Tuple, util::EPSILON};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShapeKind {
+    Sphere,
+    Plane,
+    Cube,
+}
+
+impl ShapeKind {
+    pub const ALL: [ShapeKind; 3] = [ShapeKind::Sphere, ShapeKind::Plane, ShapeKind::Cube];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShapeKind::Sphere => "sphere",
+            ShapeKind::Plane => "plane",
+            ShapeKind::Cube => "cube",
+        }
+    }
+}
+
+// Every kind is defined in its own object space (a unit sphere at the origin, the xz
+// plane, the cube from -1 to 1) and placed in the world by its transform.
+#[derive(Debug, Clone)]
+pub struct Shape {
+    pub kind: ShapeKind,
+    pub material: Material,
+    transform: Matrix,
+    inverse: Matrix,
+    normal_transform: Matrix,
+}
+
+impl Shape {
+    pub fn new(kind: ShapeKind) -> Self {
+        Self {
+            kind,
+            material: Material::default(),
+            transform: Matrix::identity(4),
+            inverse: Matrix::identity(4),
+            normal_transform: Matrix::identity(4),
+        }
+    }
+
+    pub fn transform(&self) -> &Matrix {
+        &self.transform
+    }
+
+    pub fn inverse(&self) -> &Matrix {
+        &self.inverse
+    }
+
+    pub fn set_transform(&mut self, transform: Matrix) -> Result<()> {
+        self.inverse = transform.inverse()?;
+        self.normal_transform = self.inverse.transpose();
+        self.transform = transform;
+        Ok(())
+    }
+
+    // The distances along `ray` at which it enters or leaves the shape, unsorted.
+    pub fn intersect(&self, ray: &Ray) -> Vec<f64> {
+        let local = ray.transform(&self.inverse);
+        match self.kind {
+            ShapeKind::Sphere => intersect_sphere(&local),
+            ShapeKind::Plane => intersect_plane(&local),
+            ShapeKind::Cube => intersect_cube(&local),
+        }
+    }
+
+    pub fn normal_at(&self, point: Tuple) -> Tuple {
+        let local_point = &self.inverse * point;
+        let local_normal = match self.kind {
+            ShapeKind::Sphere => local_point - Tuple::point(0.0, 0.0, 0.0),
+            ShapeKind::Plane => Tuple::vector(0.0, 1.0, 0.0),
+            ShapeKind::Cube => {
+                let (x, y, z) = (local_point.x.abs(), local_point.y.abs(), local_point.z.abs());
+                if x >= y && x >= z {
+                    Tuple::vector(local_point.x, 0.0, 0.0)
+                } else if y >= z {
+                    Tuple::vector(0.0, local_point.y, 0.0)
+                } else {
+                    Tuple::vector(0.0, 0.0, local_point.z)
+                }
+            }
+        };
+        let mut world_normal = &self.normal_transform * local_normal;
+        world_normal.w = 0.0;
+        world_normal.normalize()
+    }
+}
+
+fn intersect_sphere(ray: &Ray) -> Vec<f64> {
+    let sphere_to_ray = ray.origin - Tuple::point(0.0, 0.0, 0.0);
+    let a = ray.direction.dot(&ray.direction);
+    let b = 2.0 * ray.direction.dot(&sphere_to_ray);
+    let c = sphere_to_ray.dot(&sphere_to_ray) - 1.0;
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return Vec::new();
+    }
+    let root = discriminant.sqrt();
+    vec![(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+}
+
+fn intersect_plane(ray: &Ray) -> Vec<f64> {
+    if ray.direction.y.abs() < EPSILON {
+        return Vec::new();
+    }
+    vec![-ray.origin.y / ray.direction.y]
+}
+
+fn check_axis(origin: f64, direction: f64) -> (f64, f64) {
+    let (min, max) = if direction.abs() >= EPSILON {
+        ((-1.0 - origin) / direction, (1.0 - origin) / direction)
+    } else {
+        ((-1.0 - origin) * f64::INFINITY, (1.0 - origin) * f64::INFINITY)
+    };
+    if min > max {
+        (max, min)
+    } else {
+        (min, max)
+    }
+}
+
+fn intersect_cube(ray: &Ray) -> Vec<f64> {
+    let (x_min, x_max) = check_axis(ray.origin.x, ray.direction.x);
+    let (y_min, y_max) = check_axis(ray.origin.y, ray.direction.y);
+    let (z_min, z_max) = check_axis(ray.origin.z, ray.direction.z);
+    let t_min = x_min.max(y_min).max(z_min);
+    let t_max = x_max.min(y_max).min(z_max);
+    if t_min > t_max {
+        return Vec::new();
+    }
+    vec![t_min, t_max]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::{FRAC_1_SQRT_2, PI};
+
+    use super::*;
+    use crate::features::error::Error;
+
+    #[test]
+    fn rays_intersect_spheres_at_two_points() {
+        let sphere = Shape::new(ShapeKind::Sphere);
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(sphere.intersect(&ray), vec![4.0, 6.0]);
+        let ray = Ray::new(Tuple::point(0.0, 2.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert!(sphere.intersect(&ray).is_empty());
+    }
+
+    #[test]
+    fn intersecting_a_transformed_sphere() {
+        let mut sphere = Shape::new(ShapeKind::Sphere);
+        sphere.set_transform(Matrix::scaling(2.0, 2.0, 2.0)).unwrap();
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(sphere.intersect(&ray), vec![3.0, 7.0]);
+    }
+
+    #[test]
+    fn singular_transforms_are_rejected() {
+        let mut sphere = Shape::new(ShapeKind::Sphere);
+        assert!(matches!(sphere.set_transform(Matrix::scaling(0.0, 1.0, 1.0)), Err(Error::NotInvertible)));
+        assert_eq!(*sphere.transform(), Matrix::identity(4));
+    }
+
+    #[test]
+    fn normals_of_transformed_spheres() {
+        let mut sphere = Shape::new(ShapeKind::Sphere);
+        sphere.set_transform(Matrix::translation(0.0, 1.0, 0.0)).unwrap();
+        let normal = sphere.normal_at(Tuple::point(0.0, 1.0 + FRAC_1_SQRT_2, -FRAC_1_SQRT_2));
+        assert!((normal - Tuple::vector(0.0, FRAC_1_SQRT_2, -FRAC_1_SQRT_2)).magnitude() < 1e-9);
+
+        sphere.set_transform(Matrix::scaling(1.0, 0.5, 1.0) * Matrix::rotation_z(PI / 5.0)).unwrap();
+        let normal = sphere.normal_at(Tuple::point(0.0, 2f64.sqrt() / 2.0, -(2f64.sqrt()) / 2.0));
+        assert!((normal - Tuple::vector(0.0, 0.97014, -0.24254)).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn intersecting_planes() {
+        let plane = Shape::new(ShapeKind::Plane);
+        assert!(plane.intersect(&Ray::new(Tuple::point(0.0, 10.0, 0.0), Tuple::vector(0.0, 0.0, 1.0))).is_empty());
+        assert_eq!(plane.intersect(&Ray::new(Tuple::point(0.0, -1.0, 0.0), Tuple::vector(0.0, 1.0, 0.0))), vec![1.0]);
+        assert_eq!(plane.normal_at(Tuple::point(10.0, 0.0, -10.0)), Tuple::vector(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn intersecting_cubes() {
+        let cube = Shape::new(ShapeKind::Cube);
+        let cases = [
+            (Tuple::point(5.0, 0.5, 0.0), Tuple::vector(-1.0, 0.0, 0.0), 4.0, 6.0),
+            (Tuple::point(0.5, 0.0, 5.0), Tuple::vector(0.0, 0.0, -1.0), 4.0, 6.0),
+            (Tuple::point(0.0, 0.5, 0.0), Tuple::vector(0.0, 0.0, 1.0), -1.0, 1.0),
+        ];
+        for (origin, direction, t1, t2) in cases {
+            assert_eq!(cube.intersect(&Ray::new(origin, direction)), vec![t1, t2]);
+        }
+        assert!(cube.intersect(&Ray::new(Tuple::point(-2.0, 0.0, 0.0), Tuple::vector(0.2673, 0.5345, 0.8018))).is_empty());
+        assert_eq!(cube.normal_at(Tuple::point(1.0, 0.5, -0.8)), Tuple::vector(1.0, 0.0, 0.0));
+        assert_eq!(cube.normal_at(Tuple::point(-0.4, 0.3, -1.0)), Tuple::vector(0.0, 0.0, -1.0));
+    }
+}
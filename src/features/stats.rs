@@ -0,0 +1,198 @@
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use super::shape::ShapeKind;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RayKind {
+    Camera,
+    Shadow,
+    Reflection,
+    Refraction,
+    Diffuse,
+}
+
+// Live counters, shared by every render thread. Relaxed atomics are enough since
+// nothing is read until the render has finished.
+#[derive(Debug, Default)]
+pub struct Counters {
+    rays: [AtomicU64; 5],
+    tests: [AtomicU64; 3],
+    hits: [AtomicU64; 3],
+}
+
+impl Counters {
+    pub fn count_ray(&self, kind: RayKind) {
+        self.rays[kind as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count_test(&self, kind: ShapeKind, hit: bool) {
+        self.tests[kind as usize].fetch_add(1, Ordering::Relaxed);
+        if hit {
+            self.hits[kind as usize].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn reset(&self) {
+        self.rays.iter().chain(&self.tests).chain(&self.hits).for_each(|counter| counter.store(0, Ordering::Relaxed));
+    }
+
+    pub fn snapshot(&self) -> RenderStats {
+        let ray = |kind: RayKind| self.rays[kind as usize].load(Ordering::Relaxed);
+        RenderStats {
+            camera_rays: ray(RayKind::Camera),
+            shadow_rays: ray(RayKind::Shadow),
+            reflection_rays: ray(RayKind::Reflection),
+            refraction_rays: ray(RayKind::Refraction),
+            diffuse_rays: ray(RayKind::Diffuse),
+            shapes: ShapeKind::ALL
+                .iter()
+                .map(|&kind| ShapeStats {
+                    kind,
+                    tests: self.tests[kind as usize].load(Ordering::Relaxed),
+                    hits: self.hits[kind as usize].load(Ordering::Relaxed),
+                })
+                .collect(),
+            bvh_nodes_visited: 0,
+            phases: Vec::new(),
+        }
+    }
+}
+
+impl Clone for Counters {
+    fn clone(&self) -> Self {
+        let copy = |counters: &[AtomicU64]| counters.iter().map(|counter| AtomicU64::new(counter.load(Ordering::Relaxed))).collect::<Vec<_>>();
+        let (rays, tests, hits) = (copy(&self.rays), copy(&self.tests), copy(&self.hits));
+        Self { rays: rays.try_into().unwrap(), tests: tests.try_into().unwrap(), hits: hits.try_into().unwrap() }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShapeStats {
+    pub kind: ShapeKind,
+    pub tests: u64,
+    pub hits: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Phase {
+    pub name: String,
+    pub duration: Duration,
+}
+
+// `bvh_nodes_visited` is always 0 for now: worlds are searched linearly and there is
+// no BVH to count visits in yet. It is reported anyway so the JSON keeps its shape
+// once one is added.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    pub camera_rays: u64,
+    pub shadow_rays: u64,
+    pub reflection_rays: u64,
+    pub refraction_rays: u64,
+    pub diffuse_rays: u64,
+    pub shapes: Vec<ShapeStats>,
+    pub bvh_nodes_visited: u64,
+    pub phases: Vec<Phase>,
+}
+
+impl RenderStats {
+    pub fn total_rays(&self) -> u64 {
+        self.camera_rays + self.shadow_rays + self.reflection_rays + self.refraction_rays + self.diffuse_rays
+    }
+
+    pub fn record_phase(&mut self, name: &str, duration: Duration) {
+        self.phases.push(Phase { name: name.to_string(), duration });
+    }
+
+    pub fn to_json(&self) -> String {
+        let shapes: Vec<String> = self
+            .shapes
+            .iter()
+            .map(|shape| format!("\"{}\": {{\"tests\": {}, \"hits\": {}}}", shape.kind.name(), shape.tests, shape.hits))
+            .collect();
+        let phases: Vec<String> = self.phases.iter().map(|phase| format!("\"{}\": {}", escape(&phase.name), phase.duration.as_secs_f64())).collect();
+        format!(
+            "{{\"rays\": {{\"camera\": {}, \"shadow\": {}, \"reflection\": {}, \"refraction\": {}, \"diffuse\": {}, \"total\": {}}}, \"intersections\": {{{}}}, \"bvh_nodes_visited\": {}, \"phases\": {{{}}}}}",
+            self.camera_rays,
+            self.shadow_rays,
+            self.reflection_rays,
+            self.refraction_rays,
+            self.diffuse_rays,
+            self.total_rays(),
+            shapes.join(", "),
+            self.bvh_nodes_visited,
+            phases.join(", ")
+        )
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.chars()
+        .flat_map(|c| match c {
+            '"' | '\\' => vec!['\\', c],
+            c if c.is_control() => format!("\\u{:04x}", c as u32).chars().collect(),
+            c => vec![c],
+        })
+        .collect()
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "rays: {} camera, {} shadow, {} reflection, {} refraction, {} diffuse ({} total)",
+            self.camera_rays,
+            self.shadow_rays,
+            self.reflection_rays,
+            self.refraction_rays,
+            self.diffuse_rays,
+            self.total_rays()
+        )?;
+        for shape in self.shapes.iter().filter(|shape| shape.tests > 0) {
+            let rate = shape.hits as f64 / shape.tests as f64 * 100.0;
+            writeln!(f, "{}: {} intersection tests, {} hits ({:.1}%)", shape.kind.name(), shape.tests, shape.hits, rate)?;
+        }
+        let phases: Vec<String> = self.phases.iter().map(|phase| format!("{} {:.3}s", phase.name, phase.duration.as_secs_f64())).collect();
+        write!(f, "time: {}", phases.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counting_rays_and_tests() {
+        let counters = Counters::default();
+        counters.count_ray(RayKind::Camera);
+        counters.count_ray(RayKind::Shadow);
+        counters.count_ray(RayKind::Shadow);
+        counters.count_test(ShapeKind::Cube, true);
+        counters.count_test(ShapeKind::Cube, false);
+        let stats = counters.snapshot();
+        assert_eq!((stats.camera_rays, stats.shadow_rays, stats.total_rays()), (1, 2, 3));
+        assert_eq!(stats.shapes[ShapeKind::Cube as usize], ShapeStats { kind: ShapeKind::Cube, tests: 2, hits: 1 });
+        assert_eq!(counters.clone().snapshot(), stats);
+
+        counters.reset();
+        assert_eq!(counters.snapshot().total_rays(), 0);
+    }
+
+    #[test]
+    fn statistics_as_json() {
+        let counters = Counters::default();
+        counters.count_ray(RayKind::Reflection);
+        counters.count_test(ShapeKind::Sphere, true);
+        let mut stats = counters.snapshot();
+        stats.record_phase("render", Duration::from_millis(1500));
+        assert_eq!(
+            stats.to_json(),
+            "{\"rays\": {\"camera\": 0, \"shadow\": 0, \"reflection\": 1, \"refraction\": 0, \"diffuse\": 0, \"total\": 1}, \
+             \"intersections\": {\"sphere\": {\"tests\": 1, \"hits\": 1}, \"plane\": {\"tests\": 0, \"hits\": 0}, \"cube\": {\"tests\": 0, \"hits\": 0}}, \
+             \"bvh_nodes_visited\": 0, \"phases\": {\"render\": 1.5}}"
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use std::{f64::consts::PI, fmt, sync::Arc};
+
+use super::{canvas::Canvas, color::Color, error::Result, matrix::Matrix, tuple::Tuple};
+
+// Ways of unwrapping a point in pattern space onto the unit square, with u running
+// left to right and v bottom to top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UvMapping {
+    // Longitude and latitude on the unit sphere, for globes.
+    Spherical,
+    // The xz plane, repeating every unit.
+    Planar,
+    // Around the y axis, repeating every unit of height.
+    Cylindrical,
+    // The faces of the cube from -1 to 1, laid out as a horizontal cross: the top
+    // row holds up, the middle row left, front, right and back, the bottom row down.
+    Cube,
+}
+
+impl UvMapping {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "spherical" => Some(UvMapping::Spherical),
+            "planar" => Some(UvMapping::Planar),
+            "cylindrical" => Some(UvMapping::Cylindrical),
+            "cube" => Some(UvMapping::Cube),
+            _ => None,
+        }
+    }
+
+    pub fn map(&self, point: Tuple) -> (f64, f64) {
+        match self {
+            UvMapping::Spherical => {
+                let theta = point.x.atan2(point.z);
+                let radius = Tuple::vector(point.x, point.y, point.z).magnitude();
+                let phi = (point.y / radius).clamp(-1.0, 1.0).acos();
+                (1.0 - (theta / (2.0 * PI) + 0.5), 1.0 - phi / PI)
+            }
+            UvMapping::Planar => (point.x.rem_euclid(1.0), point.z.rem_euclid(1.0)),
+            UvMapping::Cylindrical => {
+                let theta = point.x.atan2(point.z);
+                (1.0 - (theta / (2.0 * PI) + 0.5), point.y.rem_euclid(1.0))
+            }
+            UvMapping::Cube => {
+                let (face, u, v) = cube_face_uv(point);
+                let (column, row) = match face {
+                    CubeFace::Left => (0.0, 1.0),
+                    CubeFace::Front => (1.0, 1.0),
+                    CubeFace::Right => (2.0, 1.0),
+                    CubeFace::Back => (3.0, 1.0),
+                    CubeFace::Up => (1.0, 2.0),
+                    CubeFace::Down => (1.0, 0.0),
+                };
+                ((column + u) / 4.0, (row + v) / 3.0)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CubeFace {
+    Left,
+    Right,
+    Front,
+    Back,
+    Up,
+    Down,
+}
+
+// The face a point on the cube lies on, and where on that face, as seen from
+// outside the cube with the face's up towards +y (or -z for the top, +z for the bottom).
+fn cube_face_uv(point: Tuple) -> (CubeFace, f64, f64) {
+    let (x, y, z) = (point.x, point.y, point.z);
+    let coordinate = x.abs().max(y.abs()).max(z.abs());
+    let wrap = |value: f64| value.rem_euclid(2.0) / 2.0;
+    if coordinate == x {
+        (CubeFace::Right, wrap(1.0 - z), wrap(y + 1.0))
+    } else if coordinate == -x {
+        (CubeFace::Left, wrap(z + 1.0), wrap(y + 1.0))
+    } else if coordinate == y {
+        (CubeFace::Up, wrap(x + 1.0), wrap(1.0 - z))
+    } else if coordinate == -y {
+        (CubeFace::Down, wrap(x + 1.0), wrap(z + 1.0))
+    } else if coordinate == z {
+        (CubeFace::Front, wrap(x + 1.0), wrap(y + 1.0))
+    } else {
+        (CubeFace::Back, wrap(1.0 - x), wrap(y + 1.0))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFilter {
+    Nearest,
+    Bilinear,
+}
+
+impl TextureFilter {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "nearest" => Some(TextureFilter::Nearest),
+            "bilinear" => Some(TextureFilter::Bilinear),
+            _ => None,
+        }
+    }
+}
+
+// An image wrapped onto a shape. The image is shared, so any number of materials
+// can use one loaded canvas, and the transform places the texture relative to the
+// shape it is on, like a shape's transform places it in the world.
+#[derive(Clone)]
+pub struct ImageTexture {
+    pub image: Arc<Canvas>,
+    pub mapping: UvMapping,
+    pub filter: TextureFilter,
+    transform: Matrix,
+    inverse: Matrix,
+}
+
+impl ImageTexture {
+    pub fn new(image: Arc<Canvas>, mapping: UvMapping, filter: TextureFilter) -> Self {
+        Self { image, mapping, filter, transform: Matrix::identity(4), inverse: Matrix::identity(4) }
+    }
+
+    pub fn transform(&self) -> &Matrix {
+        &self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: Matrix) -> Result<()> {
+        self.inverse = transform.inverse()?;
+        self.transform = transform;
+        Ok(())
+    }
+
+    // Where a point in the object space of the shape the texture is on lands on the image.
+    pub fn uv_at(&self, object_point: Tuple) -> (f64, f64) {
+        self.mapping.map(&self.inverse * object_point)
+    }
+
+    pub fn color_at(&self, object_point: Tuple) -> Color {
+        let (u, v) = self.uv_at(object_point);
+        self.uv_color(u, v)
+    }
+
+    // (0, 0) is the bottom left corner of the image and (1, 1) the top right. An
+    // empty image is black everywhere.
+    pub fn uv_color(&self, u: f64, v: f64) -> Color {
+        let image = &self.image;
+        if image.width == 0 || image.height == 0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let x = u.clamp(0.0, 1.0) * (image.width - 1) as f64;
+        let y = (1.0 - v.clamp(0.0, 1.0)) * (image.height - 1) as f64;
+        match self.filter {
+            TextureFilter::Nearest => image.pixel_at(x.round() as usize, y.round() as usize),
+            TextureFilter::Bilinear => {
+                let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+                let (x1, y1) = ((x0 + 1).min(image.width - 1), (y0 + 1).min(image.height - 1));
+                let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+                let top = image.pixel_at(x0, y0) * (1.0 - fx) + image.pixel_at(x1, y0) * fx;
+                let bottom = image.pixel_at(x0, y1) * (1.0 - fx) + image.pixel_at(x1, y1) * fx;
+                top * (1.0 - fy) + bottom * fy
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ImageTexture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImageTexture")
+            .field("image", &format_args!("{}x{} canvas", self.image.width, self.image.height))
+            .field("mapping", &self.mapping)
+            .field("filter", &self.filter)
+            .field("transform", &self.transform)
+            .finish()
+    }
+}
+
+// Two textures are the same if they share the image rather than hold equal pixels.
+impl PartialEq for ImageTexture {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.image, &other.image) && self.mapping == other.mapping && self.filter == other.filter && self.transform == other.transform
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: (f64, f64), b: (f64, f64)) -> bool {
+        (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9
+    }
+
+    #[test]
+    fn spherical_mapping() {
+        let half = 2f64.sqrt() / 2.0;
+        let cases = [
+            ((0.0, 0.0, -1.0), (0.0, 0.5)),
+            ((1.0, 0.0, 0.0), (0.25, 0.5)),
+            ((0.0, 0.0, 1.0), (0.5, 0.5)),
+            ((-1.0, 0.0, 0.0), (0.75, 0.5)),
+            ((0.0, 1.0, 0.0), (0.5, 1.0)),
+            ((0.0, -1.0, 0.0), (0.5, 0.0)),
+            ((half, half, 0.0), (0.25, 0.75)),
+        ];
+        for ((x, y, z), uv) in cases {
+            assert!(close(UvMapping::Spherical.map(Tuple::point(x, y, z)), uv), "({}, {}, {})", x, y, z);
+        }
+    }
+
+    #[test]
+    fn planar_and_cylindrical_mapping() {
+        assert!(close(UvMapping::Planar.map(Tuple::point(0.25, 0.0, 0.5)), (0.25, 0.5)));
+        assert!(close(UvMapping::Planar.map(Tuple::point(1.25, 0.0, -0.25)), (0.25, 0.75)));
+        assert!(close(UvMapping::Cylindrical.map(Tuple::point(0.0, 0.0, -1.0)), (0.0, 0.0)));
+        assert!(close(UvMapping::Cylindrical.map(Tuple::point(0.0, 0.5, -1.0)), (0.0, 0.5)));
+        assert!(close(UvMapping::Cylindrical.map(Tuple::point(1.0, 1.25, 0.0)), (0.25, 0.25)));
+    }
+
+    #[test]
+    fn cube_faces_and_their_uvs() {
+        let cases = [
+            ((-1.0, 0.5, -0.25), CubeFace::Left, (0.375, 0.75)),
+            ((1.1, -0.75, 0.8), CubeFace::Right, (0.1, 0.125)),
+            ((0.1, 0.6, 0.9), CubeFace::Front, (0.55, 0.8)),
+            ((-0.7, 0.0, -2.0), CubeFace::Back, (0.85, 0.5)),
+            ((0.5, 1.0, 0.9), CubeFace::Up, (0.75, 0.05)),
+            ((-0.2, -1.3, 1.1), CubeFace::Down, (0.4, 0.05)),
+        ];
+        for ((x, y, z), face, uv) in cases {
+            let (found, u, v) = cube_face_uv(Tuple::point(x, y, z));
+            assert_eq!(found, face);
+            assert!(close((u, v), uv), "({}, {}, {}) maps to ({}, {})", x, y, z, u, v);
+        }
+        // The front face is the second cell of the middle row of the cross.
+        assert!(close(UvMapping::Cube.map(Tuple::point(0.0, 0.0, 1.0)), (0.375, 0.5)));
+        assert!(close(UvMapping::Cube.map(Tuple::point(0.0, 1.0, 0.0)), (0.375, 2.5 / 3.0)));
+    }
+
+    fn gradient() -> Arc<Canvas> {
+        let mut canvas = Canvas::new(2, 2);
+        canvas.write_pixel(0, 0, Color::new(0.0, 0.0, 0.0));
+        canvas.write_pixel(1, 0, Color::new(1.0, 0.0, 0.0));
+        canvas.write_pixel(0, 1, Color::new(0.0, 1.0, 0.0));
+        canvas.write_pixel(1, 1, Color::new(1.0, 1.0, 0.0));
+        Arc::new(canvas)
+    }
+
+    #[test]
+    fn sampling_with_nearest_and_bilinear_filtering() {
+        let nearest = ImageTexture::new(gradient(), UvMapping::Planar, TextureFilter::Nearest);
+        assert_eq!(nearest.uv_color(0.0, 1.0), Color::new(0.0, 0.0, 0.0));
+        assert_eq!(nearest.uv_color(0.8, 0.2), Color::new(1.0, 1.0, 0.0));
+        let bilinear = ImageTexture { filter: TextureFilter::Bilinear, ..nearest };
+        assert_eq!(bilinear.uv_color(0.5, 0.5), Color::new(0.5, 0.5, 0.0));
+        assert_eq!(bilinear.uv_color(0.25, 0.0), Color::new(0.25, 1.0, 0.0));
+        assert_eq!(bilinear.uv_color(1.0, 1.0), Color::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn empty_images_sample_as_black() {
+        for filter in [TextureFilter::Nearest, TextureFilter::Bilinear] {
+            let texture = ImageTexture::new(Arc::new(Canvas::new(0, 0)), UvMapping::Planar, filter);
+            assert_eq!(texture.uv_color(0.5, 0.5), Color::new(0.0, 0.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn textures_are_placed_by_their_transform() {
+        let mut texture = ImageTexture::new(gradient(), UvMapping::Planar, TextureFilter::Nearest);
+        texture.set_transform(Matrix::translation(0.5, 0.0, 0.0)).unwrap();
+        assert_eq!(texture.color_at(Tuple::point(0.6, 0.0, 0.9)), Color::new(0.0, 0.0, 0.0));
+        assert_eq!(texture.color_at(Tuple::point(0.9, 0.0, 0.1)), Color::new(0.0, 1.0, 0.0));
+    }
+}
@@ -0,0 +1,151 @@
+use super::color::Color;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneMapping {
+    Clamp,
+    Reinhard,
+    Aces,
+    Filmic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gamma {
+    Linear,
+    Srgb,
+    Power(f64),
+}
+
+// Turns scene-referred linear colours into display values in 0.0..=1.0, ready for
+// quantization by the 8/16-bit writers. The default pipeline clamps and encodes to
+// sRGB, which is what image viewers expect; `linear` writes values as they are, for
+// canvases that already hold display values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExportPipeline {
+    pub exposure: f64,
+    pub tone_mapping: ToneMapping,
+    pub gamma: Gamma,
+}
+
+fn aces(x: f64) -> f64 {
+    (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+}
+
+fn hable(x: f64) -> f64 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+fn srgb_encode(x: f64) -> f64 {
+    if x <= 0.0031308 {
+        12.92 * x
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// The inverse of the sRGB encoding, for turning image colours back into linear light.
+pub fn srgb_decode(x: f64) -> f64 {
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+impl ExportPipeline {
+    pub fn new(exposure: f64, tone_mapping: ToneMapping, gamma: Gamma) -> Self {
+        Self { exposure, tone_mapping, gamma }
+    }
+
+    pub fn linear() -> Self {
+        Self::new(0.0, ToneMapping::Clamp, Gamma::Linear)
+    }
+
+    pub fn map_channel(&self, channel: f64) -> f64 {
+        let exposed = channel.max(0.0) * 2f64.powf(self.exposure);
+        let mapped = match self.tone_mapping {
+            ToneMapping::Clamp => exposed,
+            ToneMapping::Reinhard => exposed / (1.0 + exposed),
+            ToneMapping::Aces => aces(exposed),
+            ToneMapping::Filmic => {
+                const WHITE_POINT: f64 = 11.2;
+                hable(2.0 * exposed) / hable(WHITE_POINT)
+            }
+        }
+        .clamp(0.0, 1.0);
+        match self.gamma {
+            Gamma::Linear => mapped,
+            Gamma::Srgb => srgb_encode(mapped),
+            Gamma::Power(gamma) => mapped.powf(1.0 / gamma),
+        }
+    }
+
+    pub fn apply(&self, color: Color) -> Color {
+        Color::new(self.map_channel(color.red), self.map_channel(color.green), self.map_channel(color.blue))
+    }
+}
+
+impl Default for ExportPipeline {
+    fn default() -> Self {
+        Self::new(0.0, ToneMapping::Clamp, Gamma::Srgb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::util::almost_equal;
+
+    #[test]
+    fn default_pipeline_clamps_and_encodes_srgb() {
+        let pipeline = ExportPipeline::default();
+        assert_eq!(pipeline.apply(Color::new(-0.5, 0.0, 1.5)), Color::new(0.0, 0.0, 1.0));
+        assert!((pipeline.map_channel(0.2140) - 0.5).abs() < 1e-3);
+        assert_eq!(ExportPipeline::linear().apply(Color::new(-0.5, 0.25, 1.5)), Color::new(0.0, 0.25, 1.0));
+    }
+
+    #[test]
+    fn exposure_is_measured_in_stops() {
+        let pipeline = ExportPipeline::new(1.0, ToneMapping::Clamp, Gamma::Linear);
+        assert_eq!(pipeline.apply(Color::new(0.25, 0.1, 0.0)), Color::new(0.5, 0.2, 0.0));
+    }
+
+    #[test]
+    fn reinhard_compresses_highlights() {
+        let pipeline = ExportPipeline::new(0.0, ToneMapping::Reinhard, Gamma::Linear);
+        assert_eq!(pipeline.apply(Color::new(1.0, 3.0, 0.0)), Color::new(0.5, 0.75, 0.0));
+    }
+
+    #[test]
+    fn tone_curves_are_monotonic_and_bounded() {
+        for tone_mapping in [ToneMapping::Reinhard, ToneMapping::Aces, ToneMapping::Filmic] {
+            let pipeline = ExportPipeline::new(0.0, tone_mapping, Gamma::Linear);
+            let mut previous = pipeline.map_channel(0.0);
+            assert!(previous.abs() < 0.01);
+            for step in 1..100 {
+                let value = pipeline.map_channel(step as f64 * 0.5);
+                assert!(value >= previous);
+                assert!(value <= 1.0);
+                previous = value;
+            }
+        }
+    }
+
+    #[test]
+    fn srgb_gamma_encoding() {
+        let pipeline = ExportPipeline::new(0.0, ToneMapping::Clamp, Gamma::Srgb);
+        assert!(almost_equal(pipeline.map_channel(0.0), 0.0));
+        assert!(almost_equal(pipeline.map_channel(1.0), 1.0));
+        assert!((pipeline.map_channel(0.2140) - 0.5).abs() < 1e-3);
+        assert!(almost_equal(pipeline.map_channel(0.001), 0.01292));
+        for x in [0.0, 0.002, 0.3, 0.75, 1.0] {
+            assert!(almost_equal(srgb_decode(pipeline.map_channel(x)), x));
+        }
+    }
+
+    #[test]
+    fn power_gamma_encoding() {
+        let pipeline = ExportPipeline::new(0.0, ToneMapping::Clamp, Gamma::Power(2.0));
+        assert!(almost_equal(pipeline.map_channel(0.25), 0.5));
+    }
+}
@@ -4,7 +4,6 @@ use std::ops::Mul;
 use std::ops::Div;
 use std::ops::Neg;
 use super::util::almost_equal;
-use super::matrix::Matrix;
 #[derive(Debug, Clone, Copy)]
 pub struct Tuple {
     pub x: f64,
@@ -60,6 +59,17 @@ impl Tuple {
             w: self.w / m,
         }
     }
+
+    pub fn reflect(&self, normal: &Self) -> Self {
+        *self - *normal * 2.0 * self.dot(normal)
+    }
+
+    // Two unit vectors that make a right-handed orthonormal frame with this unit vector.
+    pub fn tangent_frame(&self) -> (Self, Self) {
+        let helper = if self.x.abs() > 0.9 { Self::vector(0.0, 1.0, 0.0) } else { Self::vector(1.0, 0.0, 0.0) };
+        let tangent = helper.cross(self).normalize();
+        (tangent, self.cross(&tangent))
+    }
 }
 
 impl Add for Tuple {
@@ -146,13 +156,13 @@ mod tests {
     #[test]
     fn tuple_with_w1_is_point() {
         let a = Tuple::new(4.3, -4.2, 3.1, 1.0);
-        assert_eq!(a.is_point(), true);
+        assert!(a.is_point());
     }
 
     #[test]
     fn tuple_with_w0_is_vector() {
         let a = Tuple::new(4.3, -4.2, 3.1, 0.0);
-        assert_eq!(a.is_vector(), true);
+        assert!(a.is_vector());
     }
 
     #[test]
@@ -280,4 +290,22 @@ mod tests {
         assert_eq!(result, Tuple::vector(1.0, -2.0, 1.0));
     }
 
+    #[test]
+    fn tangent_frames_are_orthonormal() {
+        for normal in [Tuple::vector(0.0, 0.0, 1.0), Tuple::vector(1.0, 0.0, 0.0), Tuple::vector(1.0, 2.0, -2.0) / 3.0] {
+            let (tangent, bitangent) = normal.tangent_frame();
+            assert!(almost_equal(tangent.magnitude(), 1.0) && almost_equal(bitangent.magnitude(), 1.0));
+            assert!(almost_equal(tangent.dot(&normal), 0.0) && almost_equal(bitangent.dot(&normal), 0.0));
+            assert_eq!(tangent.cross(&bitangent), normal);
+        }
+    }
+
+    #[test]
+    fn reflecting_vectors() {
+        let v = Tuple::vector(1.0, -1.0, 0.0);
+        assert_eq!(v.reflect(&Tuple::vector(0.0, 1.0, 0.0)), Tuple::vector(1.0, 1.0, 0.0));
+        let v = Tuple::vector(0.0, -1.0, 0.0);
+        let n = Tuple::vector(2f64.sqrt() / 2.0, 2f64.sqrt() / 2.0, 0.0);
+        assert_eq!(v.reflect(&n), Tuple::vector(1.0, 0.0, 0.0));
+    }
 }
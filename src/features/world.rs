@@ -0,0 +1,266 @@
+use super::{
+    color::Color,
+    intersection::{hit, in_front, schlick, Computations, Intersection},
+    light::Light,
+    ray::Ray,
+    shape::Shape,
+    stats::{Counters, RayKind, RenderStats},
+    tuple::Tuple,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct World {
+    pub objects: Vec<Shape>,
+    pub lights: Vec<Light>,
+    pub counters: Counters,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The counters collected since the world was built or last reset.
+    pub fn statistics(&self) -> RenderStats {
+        self.counters.snapshot()
+    }
+
+    pub fn intersect(&self, ray: &Ray) -> Vec<Intersection<'_>> {
+        let mut intersections = Vec::new();
+        for object in &self.objects {
+            let ts = object.intersect(ray);
+            self.counters.count_test(object.kind, ts.iter().any(|&t| in_front(t)));
+            intersections.extend(ts.into_iter().map(|t| Intersection::new(t, object)));
+        }
+        intersections.sort_by(|a, b| a.t.total_cmp(&b.t));
+        intersections
+    }
+
+    // `remaining` bounds how many more reflection and refraction bounces may follow.
+    pub fn color_at(&self, ray: &Ray, remaining: usize) -> Color {
+        let intersections = self.intersect(ray);
+        match hit(&intersections) {
+            Some(hit) => self.shade_hit(&hit.prepare_computations(ray, &intersections), remaining),
+            None => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    pub fn shade_hit(&self, comps: &Computations, remaining: usize) -> Color {
+        let material = &comps.object.material;
+        let surface = self.lights.iter().fold(material.emissive, |sum, light| {
+            let intensity = self.intensity_at(light, comps.over_point);
+            sum + material.lighting(comps.object, light, comps.over_point, comps.eyev, comps.normalv, intensity)
+        });
+        let reflected = self.reflected_color(comps, remaining);
+        let refracted = self.refracted_color(comps, remaining);
+        if material.reflective > 0.0 && material.transparency > 0.0 {
+            let reflectance = schlick(comps);
+            surface + reflected * reflectance + refracted * (1.0 - reflectance)
+        } else {
+            surface + reflected + refracted
+        }
+    }
+
+    // The fraction of the light's samples visible from `point`; any light but an
+    // area light is either fully visible or fully blocked.
+    pub fn intensity_at(&self, light: &Light, point: Tuple) -> f64 {
+        let samples = light.samples(point);
+        let visible = samples.iter().filter(|sample| !self.is_occluded(point, sample.direction, sample.distance)).count();
+        visible as f64 / samples.len() as f64
+    }
+
+    pub fn is_shadowed(&self, light_position: Tuple, point: Tuple) -> bool {
+        let to_light = light_position - point;
+        self.is_occluded(point, to_light.normalize(), to_light.magnitude())
+    }
+
+    // Whether anything lies within `distance` of `point` along `direction`.
+    fn is_occluded(&self, point: Tuple, direction: Tuple, distance: f64) -> bool {
+        self.counters.count_ray(RayKind::Shadow);
+        let intersections = self.intersect(&Ray::new(point, direction));
+        hit(&intersections).is_some_and(|hit| hit.t < distance)
+    }
+
+    pub fn reflected_color(&self, comps: &Computations, remaining: usize) -> Color {
+        let reflective = comps.object.material.reflective;
+        if remaining == 0 || reflective == 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        self.counters.count_ray(RayKind::Reflection);
+        self.color_at(&Ray::new(comps.over_point, comps.reflectv), remaining - 1) * reflective
+    }
+
+    pub fn refracted_color(&self, comps: &Computations, remaining: usize) -> Color {
+        let transparency = comps.object.material.transparency;
+        if remaining == 0 || transparency == 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let ratio = comps.n1 / comps.n2;
+        let cos_i = comps.eyev.dot(&comps.normalv);
+        let sin2_t = ratio * ratio * (1.0 - cos_i * cos_i);
+        if sin2_t > 1.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let cos_t = (1.0 - sin2_t).sqrt();
+        let direction = comps.normalv * (ratio * cos_i - cos_t) - comps.eyev * ratio;
+        self.counters.count_ray(RayKind::Refraction);
+        self.color_at(&Ray::new(comps.under_point, direction), remaining - 1) * transparency
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::features::{
+        light::{AreaLight, DirectionalLight, PointLight, SpotLight},
+        matrix::Matrix,
+        shape::ShapeKind,
+    };
+
+    pub(crate) fn default_world() -> World {
+        let mut outer = Shape::new(ShapeKind::Sphere);
+        outer.material.color = Color::new(0.8, 1.0, 0.6);
+        outer.material.diffuse = 0.7;
+        outer.material.specular = 0.2;
+        let mut inner = Shape::new(ShapeKind::Sphere);
+        inner.set_transform(Matrix::scaling(0.5, 0.5, 0.5)).unwrap();
+        World {
+            objects: vec![outer, inner],
+            lights: vec![Light::Point(PointLight::new(Tuple::point(-10.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0)))],
+            ..World::default()
+        }
+    }
+
+    fn close(a: Color, b: Color) -> bool {
+        (a.red - b.red).abs() < 1e-4 && (a.green - b.green).abs() < 1e-4 && (a.blue - b.blue).abs() < 1e-4
+    }
+
+    #[test]
+    fn intersections_are_sorted() {
+        let world = default_world();
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let ts: Vec<f64> = world.intersect(&ray).iter().map(|intersection| intersection.t).collect();
+        assert_eq!(ts, vec![4.0, 4.5, 5.5, 6.0]);
+    }
+
+    #[test]
+    fn color_when_a_ray_hits_or_misses() {
+        let world = default_world();
+        let miss = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 1.0, 0.0));
+        assert_eq!(world.color_at(&miss, 5), Color::new(0.0, 0.0, 0.0));
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert!(close(world.color_at(&ray, 5), Color::new(0.38066, 0.47583, 0.2855)));
+    }
+
+    #[test]
+    fn counting_rays_and_intersection_tests() {
+        let world = default_world();
+        world.color_at(&Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0)), 5);
+        let stats = world.statistics();
+        assert_eq!((stats.shadow_rays, stats.reflection_rays, stats.refraction_rays), (1, 0, 0));
+        let spheres = stats.shapes[ShapeKind::Sphere as usize];
+        // The shadow ray's line crosses the outer sphere too, but only behind its origin.
+        assert_eq!((spheres.tests, spheres.hits), (4, 2));
+    }
+
+    #[test]
+    fn shadows() {
+        let world = default_world();
+        let light = Tuple::point(-10.0, 10.0, -10.0);
+        assert!(!world.is_shadowed(light, Tuple::point(0.0, 10.0, 0.0)));
+        assert!(world.is_shadowed(light, Tuple::point(10.0, -10.0, 10.0)));
+        assert!(!world.is_shadowed(light, Tuple::point(-20.0, 20.0, -20.0)));
+    }
+
+    #[test]
+    fn point_lights_are_all_or_nothing() {
+        let world = default_world();
+        let light = world.lights[0];
+        let cases = [
+            ((0.0, 1.0001, 0.0), 1.0),
+            ((-1.0001, 0.0, 0.0), 1.0),
+            ((0.0, 0.0, -1.0001), 1.0),
+            ((0.0, 0.0, 1.0001), 0.0),
+            ((1.0001, 0.0, 0.0), 0.0),
+            ((0.0, -1.0001, 0.0), 0.0),
+            ((0.0, 0.0, 0.0), 0.0),
+        ];
+        for ((x, y, z), expected) in cases {
+            assert_eq!(world.intensity_at(&light, Tuple::point(x, y, z)), expected);
+        }
+    }
+
+    #[test]
+    fn directional_and_spot_lights_are_shadowed() {
+        let world = default_world();
+        let sun = Light::Directional(DirectionalLight::new(Tuple::vector(0.0, -1.0, 0.0), Color::new(1.0, 1.0, 1.0)));
+        assert_eq!(world.intensity_at(&sun, Tuple::point(0.0, 1.0001, 0.0)), 1.0);
+        assert_eq!(world.intensity_at(&sun, Tuple::point(0.0, -1.0001, 0.0)), 0.0);
+        let spot = Light::Spot(SpotLight::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0), 0.5, 0.1, Color::new(1.0, 1.0, 1.0)));
+        assert_eq!(world.intensity_at(&spot, Tuple::point(0.0, 0.0, -1.0001)), 1.0);
+        assert_eq!(world.intensity_at(&spot, Tuple::point(0.0, 0.0, 1.0001)), 0.0);
+    }
+
+    #[test]
+    fn area_lights_cast_partial_shadows() {
+        let world = default_world();
+        let corner = Tuple::point(-0.5, -0.5, -5.0);
+        let mut area = AreaLight::new(corner, Tuple::vector(1.0, 0.0, 0.0), 2, Tuple::vector(0.0, 1.0, 0.0), 2, Color::new(1.0, 1.0, 1.0));
+        area.jitter = false;
+        let light = Light::Area(area);
+        let cases = [((0.0, 0.0, 2.0), 0.0), ((1.0, -1.0, 2.0), 0.25), ((1.5, 0.0, 2.0), 0.5), ((1.25, 1.25, 3.0), 0.75), ((0.0, 0.0, -2.0), 1.0)];
+        for ((x, y, z), expected) in cases {
+            assert_eq!(world.intensity_at(&light, Tuple::point(x, y, z)), expected);
+        }
+        world.counters.reset();
+        world.intensity_at(&light, Tuple::point(0.0, 0.0, -2.0));
+        assert_eq!(world.statistics().shadow_rays, 4);
+    }
+
+    #[test]
+    fn reflections_stop_at_the_recursion_limit() {
+        let mut world = default_world();
+        let mut mirror = Shape::new(ShapeKind::Plane);
+        mirror.material.reflective = 0.5;
+        mirror.set_transform(Matrix::translation(0.0, -1.0, 0.0)).unwrap();
+        world.objects.push(mirror);
+        let half = 2f64.sqrt() / 2.0;
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -3.0), Tuple::vector(0.0, -half, half));
+        let intersections = [Intersection::new(2f64.sqrt(), &world.objects[2])];
+        let comps = intersections[0].prepare_computations(&ray, &intersections);
+        assert!(close(world.reflected_color(&comps, 1), Color::new(0.19033, 0.23791, 0.14274)));
+        assert_eq!(world.reflected_color(&comps, 0), Color::new(0.0, 0.0, 0.0));
+
+        let world = World { objects: vec![mirror_plane(-1.0), mirror_plane(1.0)], lights: world.lights, ..World::default() };
+        let ray = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 1.0, 0.0));
+        world.color_at(&ray, 5);
+        assert_eq!(world.statistics().reflection_rays, 5);
+    }
+
+    fn mirror_plane(y: f64) -> Shape {
+        let mut plane = Shape::new(ShapeKind::Plane);
+        plane.material.reflective = 1.0;
+        plane.set_transform(Matrix::translation(0.0, y, 0.0)).unwrap();
+        plane
+    }
+
+    #[test]
+    fn refraction_through_a_transparent_floor() {
+        let mut world = default_world();
+        let mut floor = Shape::new(ShapeKind::Plane);
+        floor.set_transform(Matrix::translation(0.0, -1.0, 0.0)).unwrap();
+        floor.material.transparency = 0.5;
+        floor.material.refractive_index = 1.5;
+        let mut ball = Shape::new(ShapeKind::Sphere);
+        ball.material.color = Color::new(1.0, 0.0, 0.0);
+        ball.material.ambient = 0.5;
+        ball.set_transform(Matrix::translation(0.0, -3.5, -0.5)).unwrap();
+        world.objects.extend([floor, ball]);
+
+        let half = 2f64.sqrt() / 2.0;
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -3.0), Tuple::vector(0.0, -half, half));
+        let intersections = [Intersection::new(2f64.sqrt(), &world.objects[2])];
+        let comps = intersections[0].prepare_computations(&ray, &intersections);
+        assert!(close(world.shade_hit(&comps, 5), Color::new(0.93642, 0.68642, 0.68642)));
+    }
+}
@@ -0,0 +1,547 @@
+use super::scene::{Location, SceneError};
+
+// The subset of YAML that scene files need: block mappings and sequences nested by
+// indentation, flow collections, quoted and plain scalars and comments. Flow
+// collections may span lines, so JSON documents parse too.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Sequence(Vec<Node>),
+    Mapping(Vec<(String, Node)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub value: Value,
+    pub location: Location,
+}
+
+impl Node {
+    pub fn new(value: Value, location: Location) -> Self {
+        Self { value, location }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Node> {
+        match &self.value {
+            Value::Mapping(entries) => entries.iter().find(|(name, _)| name == key).map(|(_, node)| node),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match &self.value {
+            Value::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.value {
+            Value::Bool(flag) => Some(flag),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.value {
+            Value::Number(number) => Some(number),
+            _ => None,
+        }
+    }
+}
+
+pub fn parse(source: &str) -> Result<Node, SceneError> {
+    let mut parser = Parser { chars: source.chars().collect(), position: 0, line: 1, line_start: 0, depth: 0 };
+    parser.skip_blank();
+    if parser.chars[parser.position..].starts_with(&['-', '-', '-']) {
+        while !matches!(parser.peek(), None | Some('\n')) {
+            parser.advance();
+        }
+        parser.skip_blank();
+    }
+    if parser.peek().is_none() {
+        return Ok(Node::new(Value::Null, parser.location()));
+    }
+    let node = parser.block_node()?;
+    parser.skip_blank();
+    if parser.peek().is_some() {
+        return parser.error("unexpected content after the document");
+    }
+    Ok(node)
+}
+
+fn scalar(text: &str, location: Location) -> Node {
+    let starts_like_number = text.starts_with(|c: char| c.is_ascii_digit() || matches!(c, '-' | '+' | '.'));
+    let value = match text {
+        "" | "~" | "null" => Value::Null,
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => match text.parse::<f64>() {
+            Ok(number) if starts_like_number && number.is_finite() => Value::Number(number),
+            _ => Value::String(text.to_string()),
+        },
+    };
+    Node::new(value, location)
+}
+
+// Collections nested deeper than this are rejected rather than parsed recursively
+// until the stack runs out.
+const MAX_DEPTH: usize = 128;
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    line: usize,
+    line_start: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.position + offset).copied()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.line_start = self.position;
+        }
+        Some(c)
+    }
+
+    fn location(&self) -> Location {
+        Location { line: self.line, column: self.column() + 1 }
+    }
+
+    fn column(&self) -> usize {
+        self.position - self.line_start
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, SceneError> {
+        Err(SceneError::new(self.location(), message))
+    }
+
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, SceneError>) -> Result<T, SceneError> {
+        if self.depth == MAX_DEPTH {
+            return self.error("collections are nested too deeply");
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\r')) {
+            self.advance();
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.advance();
+            }
+        }
+    }
+
+    // Moves to the first token on the next line that has one, or stays put if the
+    // current line still has something left.
+    fn skip_blank(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            if self.peek() != Some('\n') {
+                break;
+            }
+            self.advance();
+        }
+    }
+
+    fn at_line_end(&self) -> bool {
+        matches!(self.peek(), None | Some('\n' | '#'))
+    }
+
+    fn end_of_line(&mut self) -> Result<(), SceneError> {
+        self.skip_spaces();
+        self.skip_comment();
+        if self.at_line_end() {
+            Ok(())
+        } else {
+            self.error("unexpected characters after value")
+        }
+    }
+
+    fn is_sequence_entry(&self) -> bool {
+        self.peek() == Some('-') && matches!(self.peek_at(1), None | Some(' ' | '\t' | '\r' | '\n'))
+    }
+
+    // Whether the current line reads `key: ...`.
+    fn is_mapping_entry(&self) -> bool {
+        let mut index = self.position;
+        match self.chars.get(index) {
+            None | Some('[' | '{') => return false,
+            Some(&quote @ ('"' | '\'')) => {
+                index += 1;
+                while let Some(&c) = self.chars.get(index) {
+                    index += 1;
+                    if c == quote {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+        while let Some(&c) = self.chars.get(index) {
+            match c {
+                '\n' => return false,
+                ':' if matches!(self.chars.get(index + 1), None | Some(' ' | '\t' | '\r' | '\n')) => return true,
+                '#' if matches!(self.chars.get(index.wrapping_sub(1)), Some(' ' | '\t')) => return false,
+                _ => index += 1,
+            }
+        }
+        false
+    }
+
+    // A block node whose first token is at the current position. Its column is the
+    // indentation that sibling entries have to match.
+    fn block_node(&mut self) -> Result<Node, SceneError> {
+        let indent = self.column();
+        if self.is_sequence_entry() {
+            self.nested(|parser| parser.block_sequence(indent))
+        } else if self.is_mapping_entry() {
+            self.nested(|parser| parser.block_mapping(indent))
+        } else {
+            let node = self.inline_value()?;
+            self.end_of_line()?;
+            Ok(node)
+        }
+    }
+
+    fn block_sequence(&mut self, indent: usize) -> Result<Node, SceneError> {
+        let location = self.location();
+        let mut items = Vec::new();
+        loop {
+            let entry = self.location();
+            self.advance();
+            self.skip_spaces();
+            if self.at_line_end() {
+                self.skip_blank();
+                if self.peek().is_some() && self.column() > indent {
+                    items.push(self.block_node()?);
+                } else {
+                    items.push(Node::new(Value::Null, entry));
+                }
+            } else {
+                items.push(self.block_node()?);
+            }
+
+            self.skip_blank();
+            if self.peek().is_none() || self.column() < indent || (self.column() == indent && !self.is_sequence_entry()) {
+                break;
+            }
+            if self.column() > indent {
+                return self.error("bad indentation of a sequence entry");
+            }
+        }
+        Ok(Node::new(Value::Sequence(items), location))
+    }
+
+    fn block_mapping(&mut self, indent: usize) -> Result<Node, SceneError> {
+        let location = self.location();
+        let mut entries: Vec<(String, Node)> = Vec::new();
+        loop {
+            let key_location = self.location();
+            let key = self.key()?;
+            self.skip_spaces();
+            let value = if self.at_line_end() {
+                let empty = self.location();
+                self.skip_blank();
+                let nested = self.column() > indent || (self.column() == indent && self.is_sequence_entry());
+                if self.peek().is_some() && nested {
+                    self.block_node()?
+                } else {
+                    Node::new(Value::Null, empty)
+                }
+            } else {
+                let node = self.inline_value()?;
+                self.end_of_line()?;
+                node
+            };
+            if entries.iter().any(|(existing, _)| *existing == key) {
+                return Err(SceneError::new(key_location, format!("duplicate key '{}'", key)));
+            }
+            entries.push((key, value));
+
+            self.skip_blank();
+            if self.peek().is_none() || self.column() < indent {
+                break;
+            }
+            if self.column() > indent || !self.is_mapping_entry() {
+                return self.error("expected a mapping key");
+            }
+        }
+        Ok(Node::new(Value::Mapping(entries), location))
+    }
+
+    // Reads `key:` and leaves the position after the colon.
+    fn key(&mut self) -> Result<String, SceneError> {
+        let key = if matches!(self.peek(), Some('"' | '\'')) {
+            let key = self.quoted()?;
+            self.skip_spaces();
+            key
+        } else {
+            let mut key = String::new();
+            while let Some(c) = self.peek() {
+                if c == ':' && matches!(self.peek_at(1), None | Some(' ' | '\t' | '\r' | '\n')) {
+                    break;
+                }
+                key.push(c);
+                self.advance();
+            }
+            key.trim_end().to_string()
+        };
+        if self.advance() != Some(':') {
+            return self.error("expected ':' after key");
+        }
+        Ok(key)
+    }
+
+    fn inline_value(&mut self) -> Result<Node, SceneError> {
+        let location = self.location();
+        match self.peek() {
+            Some('[' | '{') => self.flow_value(),
+            Some('"' | '\'') => Ok(Node::new(Value::String(self.quoted()?), location)),
+            _ => {
+                let mut text = String::new();
+                while let Some(c) = self.peek() {
+                    if c == '\n' || (c == '#' && text.ends_with([' ', '\t'])) {
+                        break;
+                    }
+                    text.push(c);
+                    self.advance();
+                }
+                Ok(scalar(text.trim(), location))
+            }
+        }
+    }
+
+    fn flow_value(&mut self) -> Result<Node, SceneError> {
+        self.skip_blank();
+        let location = self.location();
+        match self.peek() {
+            Some('[') => self.nested(|parser| parser.flow_sequence(location)),
+            Some('{') => self.nested(|parser| parser.flow_mapping(location)),
+            Some('"' | '\'') => Ok(Node::new(Value::String(self.quoted()?), location)),
+            Some(']' | '}' | ',') => self.error("expected a value"),
+            Some(_) => {
+                let mut text = String::new();
+                while let Some(c) = self.peek() {
+                    let ends_key = c == ':' && matches!(self.peek_at(1), None | Some(' ' | '\t' | '\r' | '\n'));
+                    if matches!(c, ',' | ']' | '}' | '\n') || ends_key || (c == '#' && text.ends_with([' ', '\t'])) {
+                        break;
+                    }
+                    text.push(c);
+                    self.advance();
+                }
+                Ok(scalar(text.trim(), location))
+            }
+            None => self.error("unexpected end of file"),
+        }
+    }
+
+    fn flow_sequence(&mut self, location: Location) -> Result<Node, SceneError> {
+        self.advance();
+        let mut items = Vec::new();
+        while !self.flow_end(']')? {
+            items.push(self.flow_value()?);
+            self.flow_separator(']')?;
+        }
+        Ok(Node::new(Value::Sequence(items), location))
+    }
+
+    fn flow_mapping(&mut self, location: Location) -> Result<Node, SceneError> {
+        self.advance();
+        let mut entries: Vec<(String, Node)> = Vec::new();
+        while !self.flow_end('}')? {
+            let key_location = self.location();
+            let key = match self.flow_value()? {
+                Node { value: Value::String(key), .. } => key,
+                Node { value: Value::Number(number), .. } => number.to_string(),
+                _ => return Err(SceneError::new(key_location, "expected a key")),
+            };
+            self.skip_blank();
+            if self.advance() != Some(':') {
+                return self.error("expected ':' after key");
+            }
+            let value = self.flow_value()?;
+            if entries.iter().any(|(existing, _)| *existing == key) {
+                return Err(SceneError::new(key_location, format!("duplicate key '{}'", key)));
+            }
+            entries.push((key, value));
+            self.flow_separator('}')?;
+        }
+        Ok(Node::new(Value::Mapping(entries), location))
+    }
+
+    // Consumes the closing bracket if it comes next.
+    fn flow_end(&mut self, close: char) -> Result<bool, SceneError> {
+        self.skip_blank();
+        match self.peek() {
+            Some(c) if c == close => {
+                self.advance();
+                Ok(true)
+            }
+            Some(_) => Ok(false),
+            None => self.error(format!("expected '{}'", close)),
+        }
+    }
+
+    fn flow_separator(&mut self, close: char) -> Result<(), SceneError> {
+        self.skip_blank();
+        match self.peek() {
+            Some(',') => {
+                self.advance();
+                Ok(())
+            }
+            Some(c) if c == close => Ok(()),
+            _ => self.error(format!("expected ',' or '{}'", close)),
+        }
+    }
+
+    fn quoted(&mut self) -> Result<String, SceneError> {
+        let location = self.location();
+        let quote = self.advance().unwrap();
+        let mut text = String::new();
+        loop {
+            match self.advance() {
+                None | Some('\n') => return Err(SceneError::new(location, "unterminated string")),
+                Some('\'') if quote == '\'' => {
+                    if self.peek() != Some('\'') {
+                        return Ok(text);
+                    }
+                    self.advance();
+                    text.push('\'');
+                }
+                Some('"') if quote == '"' => return Ok(text),
+                Some('\\') if quote == '"' => {
+                    let escaped = match self.advance() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some(c @ ('"' | '\\' | '/')) => c,
+                        Some('u') => {
+                            let digits: String = (0..4).filter_map(|_| self.advance()).collect();
+                            match u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32) {
+                                Some(c) => c,
+                                None => return self.error(format!("bad unicode escape '\\u{}'", digits)),
+                            }
+                        }
+                        _ => return self.error("unknown escape sequence"),
+                    };
+                    text.push(escaped);
+                }
+                Some(c) => text.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbers(node: &Node) -> Vec<f64> {
+        match &node.value {
+            Value::Sequence(items) => items.iter().map(|item| item.as_f64().unwrap()).collect(),
+            _ => panic!("not a sequence: {:?}", node),
+        }
+    }
+
+    #[test]
+    fn parsing_scalars() {
+        let location = Location { line: 1, column: 1 };
+        assert_eq!(scalar("12.5", location).value, Value::Number(12.5));
+        assert_eq!(scalar("-3", location).value, Value::Number(-3.0));
+        assert_eq!(scalar("true", location).value, Value::Bool(true));
+        assert_eq!(scalar("~", location).value, Value::Null);
+        assert_eq!(scalar("inf", location).value, Value::String("inf".to_string()));
+        assert_eq!(scalar("rotate-x", location).value, Value::String("rotate-x".to_string()));
+    }
+
+    #[test]
+    fn parsing_block_collections() {
+        let source = "\
+# a comment
+- add: camera   # trailing comment
+  from: [ 0, 1.5, -5 ]
+  nested:
+    key: 'it''s'
+- define: steps
+  value:
+  - [ translate, 1, 2, 3 ]
+  -
+    - scale
+    - 2
+";
+        let document = parse(source).unwrap();
+        let Value::Sequence(items) = &document.value else { panic!() };
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].get("add").unwrap().as_str(), Some("camera"));
+        assert_eq!(numbers(items[0].get("from").unwrap()), vec![0.0, 1.5, -5.0]);
+        assert_eq!(items[0].get("nested").unwrap().get("key").unwrap().as_str(), Some("it's"));
+
+        let Value::Sequence(steps) = &items[1].get("value").unwrap().value else { panic!() };
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[1].location, Location { line: 10, column: 5 });
+        let Value::Sequence(scale) = &steps[1].value else { panic!() };
+        assert_eq!(scale[1].as_f64(), Some(2.0));
+    }
+
+    #[test]
+    fn parsing_json() {
+        let source = r#"[
+  { "add": "light",
+    "at": [-10, 10, -10],
+    "name": "key \"light\"!" }
+]"#;
+        let document = parse(source).unwrap();
+        let Value::Sequence(items) = &document.value else { panic!() };
+        assert_eq!(numbers(items[0].get("at").unwrap()), vec![-10.0, 10.0, -10.0]);
+        assert_eq!(items[0].get("name").unwrap().as_str(), Some("key \"light\"!"));
+        assert_eq!(items[0].get("at").unwrap().location, Location { line: 3, column: 11 });
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        let error = parse("a: 1\n  b: 2\n").unwrap_err();
+        assert_eq!(error.location, Location { line: 2, column: 3 });
+
+        let error = parse("a: 1\na: 2\n").unwrap_err();
+        assert_eq!(error.message, "duplicate key 'a'");
+        assert_eq!(error.location, Location { line: 2, column: 1 });
+
+        let error = parse("key: [1, 2\n").unwrap_err();
+        assert_eq!(error.location.line, 2);
+        assert!(parse("key: \"open\n").is_err());
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        let error = parse(&format!("a: {}", "[".repeat(200_000))).unwrap_err();
+        assert_eq!(error.message, "collections are nested too deeply");
+        assert_eq!(error.location, Location { line: 1, column: 131 });
+        assert!(parse(&"- ".repeat(200_000)).is_err());
+        let nested = format!("a: {}{}", "[".repeat(100), "]".repeat(100));
+        assert!(parse(&nested).is_ok());
+    }
+}
@@ -1,66 +1,274 @@
-mod features;
+use std::{
+    env,
+    fs::{self, File},
+    io::BufWriter,
+    path::Path,
+    process::ExitCode,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
 
-use std::f64::consts::PI;
+use ray_tracer_challenge::features::canvas::{Canvas, PpmFormat};
+use ray_tracer_challenge::features::error::{Error, Result};
+use ray_tracer_challenge::features::path::Integrator;
+use ray_tracer_challenge::features::render::{Progress, Renderer};
+use ray_tracer_challenge::features::sampling::{Sampler, Strategy};
+use ray_tracer_challenge::features::scene::{SceneDescription, Severity};
 
-use features::matrix::Matrix;
+const USAGE: &str = "\
+Usage: ray-tracer-challenge <scene> [options]
 
-use crate::features::canvas::Canvas;
-use crate::features::color::Color;
-use crate::features::tuple::Tuple;
+Renders a YAML or JSON scene file.
 
-fn projectile_model() {
-    let mut projectile = (Tuple::point(0.0, 1.0, 0.0), Tuple::vector(1.0, 1.8, 0.0).normalize() * 11.25);
-    let environment = (Tuple::vector(0.0, -0.1, 0.0), Tuple::vector(-0.01, 0.0, 0.0));
+Options:
+  -o, --output <path>     where to write the image (default: the scene path with .png)
+  -f, --format <format>   ppm, png, pfm or hdr (default: from the output extension)
+      --width <pixels>    override the camera width
+      --height <pixels>   override the camera height
+  -s, --samples <count>   samples per pixel (default: 1)
+  -t, --threads <count>   worker threads, 0 for one per core (default: 0)
+  -i, --integrator <name> whitted or path (default: whitted)
+  -d, --depth <count>     maximum reflection and refraction depth, or path
+                          bounces when path tracing (default: 5)
+      --stats <path>      also write the render statistics there as JSON
+      --time-limit <secs> stop rendering after this long and write the rows
+                          finished so far
+  -h, --help              show this message";
 
-    fn tick(env: (Tuple, Tuple), proj: (Tuple, Tuple)) -> (Tuple, Tuple) {
-        let position = proj.0 + proj.1;
-        let velocity = proj.1 + env.0 + env.1;
-        (position, velocity)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ppm,
+    Png,
+    Pfm,
+    Hdr,
+}
+
+impl Format {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "ppm" => Some(Format::Ppm),
+            "png" => Some(Format::Png),
+            "pfm" => Some(Format::Pfm),
+            "hdr" => Some(Format::Hdr),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Options {
+    scene: String,
+    output: String,
+    format: Format,
+    width: Option<usize>,
+    height: Option<usize>,
+    samples: usize,
+    threads: usize,
+    integrator: Integrator,
+    depth: usize,
+    stats: Option<String>,
+    time_limit: Option<Duration>,
+}
+
+// Ok(None) means the usage text was asked for.
+fn parse_options(arguments: &[String]) -> std::result::Result<Option<Options>, String> {
+    let mut scene = None;
+    let mut output = None;
+    let mut format = None;
+    let (mut width, mut height) = (None, None);
+    let (mut samples, mut threads, mut depth) = (1, 0, 5);
+    let mut integrator = Integrator::Whitted;
+    let mut stats = None;
+    let mut time_limit = None;
+
+    let mut arguments = arguments.iter();
+    while let Some(argument) = arguments.next() {
+        let mut value = |name: &str| arguments.next().cloned().ok_or_else(|| format!("{} needs a value", name));
+        let count = |name: &str, text: String| text.parse::<usize>().map_err(|_| format!("{} expects a whole number, got '{}'", name, text));
+        match argument.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-o" | "--output" => output = Some(value(argument)?),
+            "-f" | "--format" => {
+                let name = value(argument)?;
+                format = Some(Format::from_name(&name).ok_or_else(|| format!("unknown output format '{}'", name))?);
+            }
+            "--width" => width = Some(count(argument, value(argument)?)?),
+            "--height" => height = Some(count(argument, value(argument)?)?),
+            "-s" | "--samples" => samples = count(argument, value(argument)?)?,
+            "-t" | "--threads" => threads = count(argument, value(argument)?)?,
+            "-i" | "--integrator" => {
+                let name = value(argument)?;
+                integrator = Integrator::from_name(&name).ok_or_else(|| format!("unknown integrator '{}'", name))?;
+            }
+            "-d" | "--depth" => depth = count(argument, value(argument)?)?,
+            "--stats" => stats = Some(value(argument)?),
+            "--time-limit" => time_limit = Some(Duration::from_secs(count(argument, value(argument)?)? as u64)),
+            option if option.starts_with('-') => return Err(format!("unknown option '{}'", option)),
+            path if scene.is_none() => scene = Some(path.to_string()),
+            extra => return Err(format!("unexpected argument '{}'", extra)),
+        }
+    }
+
+    let scene = scene.ok_or("no scene file given")?;
+    if width == Some(0) || height == Some(0) || samples == 0 {
+        return Err("width, height and samples must be at least 1".to_string());
+    }
+    let output = output.unwrap_or_else(|| Path::new(&scene).with_extension("png").to_string_lossy().into_owned());
+    let format = match format {
+        Some(format) => format,
+        None => Path::new(&output)
+            .extension()
+            .and_then(|extension| Format::from_name(&extension.to_string_lossy()))
+            .ok_or_else(|| format!("cannot tell the format of '{}', use --format", output))?,
+    };
+    Ok(Some(Options { scene, output, format, width, height, samples, threads, integrator, depth, stats, time_limit }))
+}
+
+// Overriding one dimension keeps the camera's aspect ratio.
+fn resolution(options: &Options, width: usize, height: usize) -> (usize, usize) {
+    match (options.width, options.height) {
+        (Some(w), Some(h)) => (w, h),
+        (Some(w), None) => (w, ((w * height) as f64 / width as f64).round().max(1.0) as usize),
+        (None, Some(h)) => (((h * width) as f64 / height as f64).round().max(1.0) as usize, h),
+        (None, None) => (width, height),
+    }
+}
+
+fn write_image(canvas: &Canvas, path: &str, format: Format) -> Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    match format {
+        Format::Ppm => canvas.write_ppm(writer, PpmFormat::Binary, 255)?,
+        Format::Png => canvas.write_png(writer)?,
+        Format::Pfm => canvas.write_pfm(writer)?,
+        Format::Hdr => canvas.write_hdr(writer)?,
+    }
+    Ok(())
+}
+
+fn render(options: &Options) -> Result<()> {
+    let start = Instant::now();
+    let mut scene = SceneDescription::from_file(&options.scene)?;
+    let diagnostics = scene.validate();
+    for diagnostic in &diagnostics {
+        eprintln!("{}: {}", options.scene, diagnostic);
     }
+    let errors = diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Error).count();
+    if errors > 0 {
+        return Err(Error::InvalidScene { errors });
+    }
+    let (width, height) = resolution(options, scene.camera.width, scene.camera.height);
+    scene.camera.width = width;
+    scene.camera.height = height;
+    let loaded = Instant::now();
+    let camera = scene.camera()?;
+    let world = scene.world()?;
+    let built = Instant::now();
 
-    let mut canvas = Canvas::new(900, 550);
-    let color = Color::new(1.0, 1.0, 0.0);
-
-    while projectile.0.y > 0.0 && projectile.0.x < canvas.width as f64 {
-        let x = projectile.0.x as usize;
-        let y = canvas.height - projectile.0.y as usize;
-        canvas.write_pixel(x, y, color);
-        canvas.write_pixel(x + 1, y, color);
-        canvas.write_pixel(x, y + 1, color);
-        canvas.write_pixel(x + 1, y + 1, color);
-        projectile = tick(environment, projectile);
+    let renderer = Renderer::new(options.threads);
+    let strategy = if options.samples == 1 { Strategy::Grid } else { Strategy::Jittered };
+    let sampler = Sampler::new(strategy, options.samples);
+    let cancel = AtomicBool::new(false);
+    let progress = |p: Progress| {
+        eprint!("\rrendered {}/{} rows", p.completed_rows, p.total_rows);
+        if options.time_limit.is_some_and(|limit| p.elapsed >= limit) {
+            cancel.store(true, Ordering::Relaxed);
+        }
+    };
+    let shader = camera.shader(&world, options.integrator, options.depth);
+    let canvas = renderer.render_sampled_with_progress(width, height, &sampler, shader, progress, &cancel);
+    let rendered = Instant::now();
+    eprintln!();
+    if cancel.load(Ordering::Relaxed) {
+        eprintln!("stopped at the time limit, the image is incomplete");
     }
+    write_image(&canvas, &options.output, options.format)?;
 
-    canvas.canvas_to_file("projectile.ppm");
+    let mut stats = world.statistics();
+    stats.record_phase("load", loaded - start);
+    stats.record_phase("build", built - loaded);
+    stats.record_phase("render", rendered - built);
+    stats.record_phase("write", rendered.elapsed());
+    eprintln!("{}: {} objects, {} lights", options.scene, world.objects.len(), world.lights.len());
+    eprintln!(
+        "rendered {}x{} with {} samples per pixel on {} threads, {:?} integrator, depth {}",
+        width,
+        height,
+        sampler.samples_per_pixel(),
+        renderer.thread_count(),
+        options.integrator,
+        options.depth
+    );
+    eprintln!("{}", stats);
+    eprintln!("wrote {}", options.output);
+    if let Some(path) = &options.stats {
+        fs::write(path, stats.to_json() + "\n")?;
+    }
+    Ok(())
 }
 
-fn draw_clock() {
-    let mut canvas = Canvas::new(600, 600);
-    let white_color = Color::new(1.0, 1.0, 1.0);
-    let magenta_color = Color::new(1.0, 0.0, 1.0);
-
-    let center = Tuple::point(canvas.width as f64 / 2.0, canvas.height as f64 / 2.0, 0.0);
-    let translation = Matrix::translation(center.x, center.y, center.z);
-    let rotation = Matrix::rotation_z(PI / 30.0);
-    let mut transform = translation.clone() * rotation.clone();
-    for index in 0..60 {
-        let point = transform.clone() * Tuple::point(0.0, -(canvas.height as f64) / 3.0, 0.0);
-        if index % 5 == 0 {
-            canvas.write_pixel(point.x as usize, point.y as usize, white_color);
-            canvas.write_pixel(point.x as usize + 1, point.y as usize, white_color);
-            canvas.write_pixel(point.x as usize, point.y as usize + 1, white_color);
-            canvas.write_pixel(point.x as usize + 1, point.y as usize + 1, white_color);
-        } else {
-            canvas.write_pixel(point.x as usize, point.y as usize, magenta_color);
-            canvas.write_pixel(point.x as usize + 1, point.y as usize, magenta_color);
-            canvas.write_pixel(point.x as usize, point.y as usize + 1, magenta_color);
-            canvas.write_pixel(point.x as usize + 1, point.y as usize + 1, magenta_color); 
-        } 
-        
-        transform = transform.clone() * rotation.clone();
+fn main() -> ExitCode {
+    let arguments: Vec<String> = env::args().skip(1).collect();
+    let options = match parse_options(&arguments) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match render(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
     }
-    canvas.canvas_to_file("clock.ppm"); 
 }
-fn main() {
-    draw_clock();
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(arguments: &[&str]) -> std::result::Result<Option<Options>, String> {
+        parse_options(&arguments.iter().map(|argument| argument.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn defaults_come_from_the_scene_path() {
+        let options = parse(&["scenes/spheres.yml"]).unwrap().unwrap();
+        assert_eq!(options.output, "scenes/spheres.png");
+        assert_eq!(options.format, Format::Png);
+        assert_eq!((options.samples, options.threads, options.depth), (1, 0, 5));
+        assert_eq!(options.integrator, Integrator::Whitted);
+        assert_eq!(options.time_limit, None);
+    }
+
+    #[test]
+    fn parsing_every_option() {
+        let arguments = ["scene.yml", "-o", "out.image", "--format", "HDR", "--width", "320", "-s", "4", "-t", "2", "-d", "3", "-i", "path", "--stats", "stats.json", "--time-limit", "60"];
+        let options = parse(&arguments).unwrap().unwrap();
+        assert_eq!(options.output, "out.image");
+        assert_eq!(options.format, Format::Hdr);
+        assert_eq!((options.width, options.height), (Some(320), None));
+        assert_eq!((options.samples, options.threads, options.depth), (4, 2, 3));
+        assert_eq!(options.integrator, Integrator::PathTracing);
+        assert_eq!(options.stats.as_deref(), Some("stats.json"));
+        assert_eq!(options.time_limit, Some(Duration::from_secs(60)));
+        assert_eq!(resolution(&options, 200, 100), (320, 160));
+        assert!(parse(&["--help"]).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejecting_bad_arguments() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["scene.yml", "--samples"]).is_err());
+        assert!(parse(&["scene.yml", "--samples", "many"]).is_err());
+        assert!(parse(&["scene.yml", "--bogus"]).is_err());
+        assert!(parse(&["scene.yml", "--integrator", "photon"]).is_err());
+        assert!(parse(&["scene.yml", "-o", "out.jpg"]).is_err());
+        assert!(parse(&["a.yml", "b.yml"]).is_err());
+    }
 }